@@ -0,0 +1,158 @@
+// HTTP-date (RFC 9110 section 5.6.7): always sent as IMF-fixdate, all three
+// historic formats accepted when parsing
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+// days since 1970-01-01 of a proleptic Gregorian date
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let yoe = year - era * 400;
+    let doy = (153 * ((month as i64 + 9) % 12) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = if z >= 0 { z } else { z - 146096 } / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// `Sun, 06 Nov 1994 08:49:37 GMT`, times before the epoch are clamped to it
+pub fn format_http_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs() as i64;
+    let (days, secs) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{}, {day:02} {} {year:04} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[(days + 4).rem_euclid(7) as usize],
+        MONTHS[month as usize - 1],
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+fn parse_month(s: &str) -> Option<u32> {
+    MONTHS.iter().position(|m| *m == s).map(|m| m as u32 + 1)
+}
+
+fn parse_time(s: &str) -> Option<u64> {
+    let mut parts = s.split(':');
+    let mut secs = 0;
+    for limit in [24, 60, 61] {
+        let part: u64 = parts.next()?.parse().ok()?;
+        if part >= limit {
+            return None;
+        }
+        secs = secs * 60 + part;
+    }
+    parts.next().is_none().then_some(secs)
+}
+
+fn to_system_time(year: i64, month: u32, day: u32, secs: u64) -> Option<SystemTime> {
+    if !(1..=31).contains(&day) || year < 1970 {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+    // reject days past the end of the month, e.g. `31 Feb`
+    if civil_from_days(days) != (year, month, day) {
+        return None;
+    }
+    Some(UNIX_EPOCH + Duration::from_secs(days as u64 * 86400 + secs))
+}
+
+// accepts IMF-fixdate, RFC 850 and asctime dates
+pub fn parse_http_date(s: &str) -> Option<SystemTime> {
+    let parts: Vec<_> = s.split_whitespace().collect();
+    match parts.as_slice() {
+        // Sun, 06 Nov 1994 08:49:37 GMT
+        [_, day, month, year, time, "GMT"] if day.len() == 2 && year.len() == 4 => to_system_time(
+            year.parse().ok()?,
+            parse_month(month)?,
+            day.parse().ok()?,
+            parse_time(time)?,
+        ),
+        // Sunday, 06-Nov-94 08:49:37 GMT
+        [_, date, time, "GMT"] => {
+            let mut date = date.split('-');
+            let (day, month, year) = (date.next()?, date.next()?, date.next()?);
+            let year: i64 = if year.len() == 2 {
+                year.parse().ok()?
+            } else {
+                return None;
+            };
+            let year = if year < 70 { 2000 + year } else { 1900 + year };
+            to_system_time(
+                year,
+                parse_month(month)?,
+                day.parse().ok()?,
+                parse_time(time)?,
+            )
+        }
+        // Sun Nov  6 08:49:37 1994
+        [_, month, day, time, year] => to_system_time(
+            year.parse().ok()?,
+            parse_month(month)?,
+            day.parse().ok()?,
+            parse_time(time)?,
+        ),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format_http_date() {
+        let time = UNIX_EPOCH + Duration::from_secs(784111777);
+        assert_eq!(format_http_date(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(
+            format_http_date(UNIX_EPOCH),
+            "Thu, 01 Jan 1970 00:00:00 GMT"
+        );
+        let leap = UNIX_EPOCH + Duration::from_secs(951782400);
+        assert_eq!(format_http_date(leap), "Tue, 29 Feb 2000 00:00:00 GMT");
+    }
+
+    #[test]
+    fn test_parse_http_date() {
+        let time = UNIX_EPOCH + Duration::from_secs(784111777);
+        for s in [
+            "Sun, 06 Nov 1994 08:49:37 GMT",
+            "Sunday, 06-Nov-94 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 1994",
+        ] {
+            assert_eq!(parse_http_date(s), Some(time), "{s}");
+        }
+        for s in [
+            "Sun, 06 Nov 1994 08:49:37 UTC",
+            "Sun, 31 Feb 1994 08:49:37 GMT",
+            "Sun, 06 Nov 1994 24:00:00 GMT",
+            "Sun, 06 Foo 1994 08:49:37 GMT",
+            "yesterday",
+        ] {
+            assert_eq!(parse_http_date(s), None, "{s}");
+        }
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(parse_http_date(&format_http_date(now)), Some(now));
+    }
+}
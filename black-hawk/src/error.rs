@@ -1,4 +1,4 @@
-use std::{num::ParseIntError, string::FromUtf8Error};
+use std::{num::ParseIntError, string::FromUtf8Error, time::Duration};
 
 use thiserror::Error;
 
@@ -26,12 +26,26 @@ pub enum RequestParseError {
     IncompleteMultipartBody,
     #[error("url decode error")]
     UrlDecode,
+    #[error("invalid request path")]
+    InvalidPath,
     #[error("multipart header parse error")]
     MultipartHeaderParse,
     #[error("unsupport MIME type {0}")]
     UnsupportMimeType(String),
     #[error("missing necessary content length")]
     MissingContentLength,
+    #[error("connection closed by peer")]
+    ConnectionClosed,
+    #[error("incomplete request header")]
+    IncompleteHeader,
+    #[error("incomplete request body")]
+    IncompleteBody,
+    #[error("request body exceeds {0} bytes")]
+    BodyTooLarge(usize),
+    #[error("request header not complete within {0:?}")]
+    HeaderTimeout(Duration),
+    #[error("request body stalled for {0:?}")]
+    BodyTimeout(Duration),
 }
 
 impl From<nom::Err<nom::error::Error<&[u8]>>> for RequestParseError {
@@ -1,31 +1,176 @@
-use std::{io::ErrorKind, path::Path};
+use std::{
+    collections::HashMap,
+    fs::Metadata,
+    io::{self, Cursor, ErrorKind, SeekFrom},
+    path::{Path, PathBuf},
+    pin::Pin,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use crate::{
     consts::ContentType,
+    date::{format_http_date, parse_http_date},
+    request::range::ByteRange,
     response::{
-        body::{IntoBinaryBody, IntoTextBody},
+        body::{Body, IntoTextBody},
         status::HttpStatus,
     },
     INDEX_FILE, WEB_ROOT,
 };
 use async_trait::async_trait;
+use tokio::{
+    fs::{self, File},
+    io::{AsyncRead, AsyncReadExt, AsyncSeekExt},
+};
 
-use crate::{request::HttpRequest, response::Response};
+use crate::{
+    request::{HttpRequest, StreamingRequest},
+    response::Response,
+};
 
 #[async_trait]
 pub trait Handler: Send + Sync {
     async fn handle(&self, request: &HttpRequest) -> anyhow::Result<Response>;
+
+    // Called with the body still on the connection. The default reads all of
+    // it and calls `handle`, override it to stream large uploads elsewhere.
+    // Whatever part of the body is left unread gets skipped afterwards.
+    async fn handle_stream(&self, request: &mut StreamingRequest<'_>) -> anyhow::Result<Response> {
+        let request = request.to_buffered().await?;
+        self.handle(&request).await
+    }
 }
 
-#[derive(Debug)]
-pub struct StaticHandler;
+// Serves files under `WEB_ROOT`. Every response carries an `ETag` built from
+// size and mtime plus `Last-Modified`, so clients can revalidate with
+// `If-None-Match`/`If-Modified-Since` and get a bodyless 304.
+#[derive(Debug, Clone)]
+pub struct StaticHandler {
+    // `Cache-Control` by file extension, `default_cache_control` otherwise
+    cache_control: HashMap<String, String>,
+    default_cache_control: Option<String>,
+}
+
+impl Default for StaticHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StaticHandler {
+    // html is revalidated every time, everything else may be cached an hour
+    pub fn new() -> Self {
+        Self {
+            cache_control: HashMap::from([("html".to_string(), "no-cache".to_string())]),
+            default_cache_control: Some("public, max-age=3600".to_string()),
+        }
+    }
+
+    pub fn with_cache_control(mut self, extension: &str, value: &str) -> Self {
+        self.cache_control
+            .insert(extension.to_string(), value.to_string());
+        self
+    }
+
+    pub fn with_default_cache_control(mut self, value: Option<&str>) -> Self {
+        self.default_cache_control = value.map(str::to_string);
+        self
+    }
+
+    fn cache_control_for(&self, path: &str) -> Option<&str> {
+        let ext = Path::new(path).extension().map(|e| e.to_string_lossy());
+        ext.and_then(|e| self.cache_control.get(e.as_ref()))
+            .or(self.default_cache_control.as_ref())
+            .map(String::as_str)
+    }
+}
+
+// strong validator from size and modification time, it changes whenever
+// the file is rewritten
+fn etag(len: u64, modified: SystemTime) -> String {
+    let mtime = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!(
+        "\"{len:x}-{:x}{:08x}\"",
+        mtime.as_secs(),
+        mtime.subsec_nanos()
+    )
+}
+
+// `If-None-Match` wins over `If-Modified-Since` when both are present,
+// tags compare weakly as RFC 9110 asks for GET
+fn not_modified(request: &HttpRequest, etag: &str, modified: SystemTime) -> bool {
+    let headers = &request.header().headers.0;
+    if let Some(tags) = headers.get("If-None-Match") {
+        let opaque = |t: &str| t.trim().trim_start_matches("W/").to_string();
+        return tags
+            .split(',')
+            .any(|t| t.trim() == "*" || opaque(t) == opaque(etag));
+    }
+    match headers
+        .get("If-Modified-Since")
+        .and_then(|d| parse_http_date(d))
+    {
+        // HTTP dates have whole seconds only
+        Some(since) => {
+            let modified = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+            let since = since.duration_since(UNIX_EPOCH).unwrap_or_default();
+            modified.as_secs() <= since.as_secs()
+        }
+        None => false,
+    }
+}
+
+fn forbidden() -> Response {
+    Response::new(
+        HttpStatus::Forbidden,
+        &ContentType::PlainText,
+        IntoTextBody::into_body("Forbidden!"),
+    )
+}
+
+// Opens `path` only if it still resolves inside `root` once symlinks are
+// followed, `None` otherwise. The normalized request path cannot climb out
+// on its own, but a link inside the root could point anywhere. Returns the
+// canonical path as well, later opens of the same file must go through
+// `reopen` with it.
+async fn open_under(root: &Path, path: &Path) -> io::Result<Option<(File, PathBuf)>> {
+    let root = fs::canonicalize(root).await?;
+    let canonical = fs::canonicalize(path).await?;
+    if !canonical.starts_with(&root) {
+        return Ok(None);
+    }
+    let file = File::open(&canonical).await?;
+    Ok(Some((file, canonical)))
+}
+
+// Another handle on the file `checked` came from, `None` if `path` no longer
+// leads to that very file, e.g. because a link was swapped in since.
+async fn reopen(path: &Path, checked: &Metadata) -> io::Result<Option<File>> {
+    let file = File::open(path).await?;
+    let metadata = file.metadata().await?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if (metadata.dev(), metadata.ino()) != (checked.dev(), checked.ino()) {
+            return Ok(None);
+        }
+    }
+    #[cfg(not(unix))]
+    if metadata.len() != checked.len() || metadata.modified()? != checked.modified()? {
+        return Ok(None);
+    }
+    Ok(Some(file))
+}
 
 #[async_trait]
 impl Handler for StaticHandler {
     async fn handle(&self, request: &HttpRequest) -> anyhow::Result<Response> {
+        let Ok(normalized) = request.header().path.normalized() else {
+            return Ok(forbidden());
+        };
         let mut path = String::new();
         path.push_str(WEB_ROOT);
-        path.push_str(request.header().path.abs_path());
+        path.push_str(&normalized);
         let p: &Path = path.as_ref();
         let ext = p.extension();
         println!("{ext:?}");
@@ -39,8 +184,9 @@ impl Handler for StaticHandler {
                 ContentType::TextHtml
             }
         };
-        let content = match tokio::fs::read(path).await {
-            Ok(c) => c,
+        let (file, canonical) = match open_under(WEB_ROOT.as_ref(), path.as_ref()).await {
+            Ok(Some(opened)) => opened,
+            Ok(None) => return Ok(forbidden()),
             Err(e) => {
                 if e.kind() == ErrorKind::NotFound {
                     return Ok(Response::new(
@@ -53,10 +199,148 @@ impl Handler for StaticHandler {
                 }
             }
         };
-        Ok(Response::new(
-            HttpStatus::Ok,
-            &content_type,
-            IntoBinaryBody::into_body(content),
-        ))
+        let metadata = file.metadata().await?;
+        let len = metadata.len();
+        let modified = metadata.modified()?;
+        let etag = etag(len, modified);
+        let mut response = if not_modified(request, &etag, modified) {
+            Response::new(
+                HttpStatus::NotModified,
+                &content_type,
+                IntoTextBody::into_body(()),
+            )
+        } else {
+            match request.header().range() {
+                Some(ranges) if ranges.len() <= MAX_RANGES => {
+                    range_response(file, &canonical, &metadata, &ranges, &content_type).await?
+                }
+                _ => Response::new(HttpStatus::Ok, &content_type, Body::from_file(file).await?),
+            }
+        };
+        response.add_header("Accept-Ranges", "bytes");
+        response.add_header("ETag", &etag);
+        response.add_header("Last-Modified", format_http_date(modified));
+        if let Some(cache_control) = self.cache_control_for(&path) {
+            response.add_header("Cache-Control", cache_control);
+        }
+        Ok(response)
+    }
+}
+
+// more ranges than this and the header is ignored, the whole file is cheaper
+// than a pile of tiny parts
+const MAX_RANGES: usize = 16;
+
+// `path` is the canonical path `file` was opened from
+async fn range_response(
+    mut file: File,
+    path: &Path,
+    metadata: &Metadata,
+    ranges: &[ByteRange],
+    content_type: &ContentType,
+) -> anyhow::Result<Response> {
+    let len = metadata.len();
+    let resolved: Vec<_> = ranges.iter().filter_map(|r| r.resolve(len)).collect();
+    match resolved.as_slice() {
+        [] => {
+            let mut response = Response::new(
+                HttpStatus::RangeNotSatisfiable,
+                &ContentType::PlainText,
+                IntoTextBody::into_body(()),
+            );
+            response.add_header("Content-Range", format!("bytes */{len}"));
+            Ok(response)
+        }
+        &[(first, last)] => {
+            file.seek(SeekFrom::Start(first)).await?;
+            let body = Body::File {
+                file,
+                len: last - first + 1,
+            };
+            let mut response = Response::new(HttpStatus::PartialContent, content_type, body);
+            response.add_header("Content-Range", format!("bytes {first}-{last}/{len}"));
+            Ok(response)
+        }
+        parts => {
+            let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+            let boundary = format!("black-hawk-{nanos:x}");
+            // every part reads from its own handle, the stream chains them
+            // with the part headers in between
+            let mut body: Pin<Box<dyn AsyncRead + Send>> = Box::pin(Cursor::new(vec![]));
+            for &(first, last) in parts {
+                let head = format!(
+                    "--{boundary}\r\nContent-Type: {}\r\nContent-Range: bytes {first}-{last}/{len}\r\n\r\n",
+                    content_type.as_str()
+                );
+                let Some(mut part) = reopen(path, metadata).await? else {
+                    return Ok(forbidden());
+                };
+                part.seek(SeekFrom::Start(first)).await?;
+                body = Box::pin(
+                    body.chain(Cursor::new(head))
+                        .chain(part.take(last - first + 1))
+                        .chain(Cursor::new("\r\n")),
+                );
+            }
+            let tail = format!("--{boundary}--\r\n");
+            let body = Body::Stream(Box::pin(body.chain(Cursor::new(tail))));
+            let mut response = Response::new(HttpStatus::PartialContent, content_type, body);
+            response.add_header(
+                "Content-Type",
+                format!("multipart/byteranges; boundary={boundary}"),
+            );
+            Ok(response)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_open_under_root() {
+        let base = std::env::temp_dir().join(format!("black-hawk-{}", std::process::id()));
+        let root = base.join("root");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("inside.txt"), "inside").unwrap();
+        std::fs::write(base.join("secret.txt"), "secret").unwrap();
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(base.join("secret.txt"), root.join("link.txt")).unwrap();
+            std::os::unix::fs::symlink(&base, root.join("up")).unwrap();
+        }
+
+        let (_, canonical) = open_under(&root, &root.join("inside.txt"))
+            .await
+            .unwrap()
+            .unwrap();
+        let checked = std::fs::metadata(&canonical).unwrap();
+        assert!(reopen(&canonical, &checked).await.unwrap().is_some());
+        assert!(open_under(&root, &root.join("../secret.txt"))
+            .await
+            .unwrap()
+            .is_none());
+        #[cfg(unix)]
+        for escape in ["link.txt", "up/secret.txt", "up/root/../secret.txt"] {
+            assert!(
+                open_under(&root, &root.join(escape))
+                    .await
+                    .unwrap()
+                    .is_none(),
+                "{escape}"
+            );
+        }
+        // a symlink swapped in after the check is caught on reopen
+        #[cfg(unix)]
+        {
+            std::fs::remove_file(&canonical).unwrap();
+            std::os::unix::fs::symlink(base.join("secret.txt"), &canonical).unwrap();
+            assert!(reopen(&canonical, &checked).await.unwrap().is_none());
+        }
+        let missing = open_under(&root, &root.join("missing.txt")).await;
+        assert_eq!(missing.unwrap_err().kind(), ErrorKind::NotFound);
+
+        std::fs::remove_dir_all(&base).unwrap();
     }
 }
@@ -1,15 +1,20 @@
-use std::sync::Arc;
+use std::{io, sync::Arc, time::Duration};
 
 use consts::ContentType;
+use error::RequestParseError;
 use handler::StaticHandler;
-use request::HttpMethod;
+use request::{HttpMethod, StreamingRequest};
 use response::{status::HttpStatus, Response};
 use route::{Router, StaticRouter};
-use tokio::{io::AsyncWriteExt, net::TcpStream};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite},
+    time::timeout,
+};
 
 pub mod consts;
+pub mod date;
 mod error;
-mod handler;
+pub mod handler;
 pub mod parse;
 pub mod request;
 pub mod response;
@@ -19,8 +24,59 @@ const DELIMITER: &[u8] = b"\r\n\r\n";
 pub const WEB_ROOT: &str = "html";
 pub const INDEX_FILE: &str = "index.html";
 
+// limits for one keep-alive connection
+#[derive(Debug, Clone, Copy)]
+pub struct ConnectionConfig {
+    // how long to wait for the next request to start before closing, a
+    // request already under way is not cut off
+    pub idle_timeout: Duration,
+    // how long a started request head may take to arrive in full, slower
+    // ones are answered with 408 and the connection closed
+    pub header_timeout: Duration,
+    // 0 is treated as 1, one request and no keep-alive
+    pub max_requests: usize,
+    // larger request bodies are answered with 413 and the connection closed
+    pub max_body_size: usize,
+    // longest a request body may stall between reads, a stalled body is
+    // answered with 408 and the connection closed
+    pub body_timeout: Duration,
+}
+
+impl Default for ConnectionConfig {
+    fn default() -> Self {
+        Self {
+            idle_timeout: Duration::from_secs(5),
+            header_timeout: Duration::from_secs(10),
+            max_requests: 100,
+            max_body_size: 16 * 1024 * 1024,
+            body_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+// When to compress responses, see `Response::compressed`
+#[derive(Debug, Clone, Copy)]
+pub struct CompressionConfig {
+    // smaller bodies go out as is, the gain would not pay for the work
+    pub min_size: u64,
+    // also compress file and stream bodies while writing them, they lose
+    // their `Content-Length` and are sent chunked
+    pub streaming: bool,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            min_size: 1024,
+            streaming: true,
+        }
+    }
+}
+
 pub struct AppContext {
     static_router: StaticRouter,
+    connection: ConnectionConfig,
+    compression: Option<CompressionConfig>,
 }
 
 // 'static并不代表生命周期是完全静态的，代表修饰的value能够在程序运行的整个生命周期中存在
@@ -28,10 +84,31 @@ impl AppContext {
     pub fn new() -> Self {
         let mut static_router = StaticRouter::new();
         // /static/images/logo.png should be handled by StaticHandler with path /images/logo.png
-        static_router.add_route(&HttpMethod::Get, "/*", Box::new(StaticHandler));
+        static_router.add_route(&HttpMethod::Get, "/*", Box::new(StaticHandler::new()));
         // /api/user/{user_id} should match any /user/12345 like path and
         // put `12345` into path variable `user_id`
-        Self { static_router }
+        Self {
+            static_router,
+            connection: ConnectionConfig::default(),
+            compression: Some(CompressionConfig::default()),
+        }
+    }
+
+    pub fn with_static_handler(mut self, handler: StaticHandler) -> Self {
+        self.static_router
+            .add_route(&HttpMethod::Get, "/*", Box::new(handler));
+        self
+    }
+
+    // `None` turns compression off
+    pub fn with_compression(mut self, compression: Option<CompressionConfig>) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn with_connection_config(mut self, connection: ConnectionConfig) -> Self {
+        self.connection = connection;
+        self
     }
 }
 
@@ -41,44 +118,609 @@ impl Default for AppContext {
     }
 }
 
-pub async fn handle_request(mut stream: TcpStream, context: Arc<AppContext>) {
-    let mut request = match request::read_http_request(&mut stream).await {
-        Ok(req) => req,
-        Err(e) => {
+// Serves requests off one connection until the client asks to close, the
+// connection sits idle for `idle_timeout`, or `max_requests` were answered.
+// Pipelined requests are read and answered strictly in order.
+pub async fn handle_request<S: AsyncRead + AsyncWrite + Unpin + Send>(
+    mut stream: S,
+    context: Arc<AppContext>,
+) {
+    let ConnectionConfig {
+        idle_timeout,
+        header_timeout,
+        max_requests,
+        max_body_size,
+        body_timeout,
+    } = context.connection;
+    let max_requests = max_requests.max(1);
+    let mut pending = vec![];
+    for served in 1..=max_requests {
+        if pending.is_empty() {
+            let mut buf = [0; 4096];
+            match timeout(idle_timeout, stream.read(&mut buf)).await {
+                Ok(Ok(0)) | Err(_) => return,
+                Ok(Ok(n)) => pending.extend_from_slice(&buf[..n]),
+                Ok(Err(e)) => {
+                    eprintln!("{e}");
+                    return;
+                }
+            }
+        }
+        let source: &mut (dyn AsyncRead + Unpin + Send) = &mut stream;
+        let read =
+            request::read_streaming_request(source, &mut pending, max_body_size, body_timeout);
+        let mut request = match timeout(header_timeout, read).await {
+            Ok(Ok(req)) => req,
+            Ok(Err(e)) => {
+                reject(&mut stream, e).await;
+                return;
+            }
+            Err(_) => {
+                reject(
+                    &mut stream,
+                    RequestParseError::HeaderTimeout(header_timeout).into(),
+                )
+                .await;
+                return;
+            }
+        };
+        let keep_alive = request.header().keep_alive() && served < max_requests;
+        let accept_encoding = request.header().headers.0.get("Accept-Encoding").cloned();
+        // the next request starts right after this body, skip what the
+        // handler did not read
+        let response = match respond(&mut request, &context).await {
+            Ok(response) => request.skip_body().await.map(|_| response),
+            Err(e) => Err(e),
+        };
+        drop(request);
+        let mut response = match response {
+            Ok(response) => response,
+            Err(e) => {
+                reject(&mut stream, e).await;
+                return;
+            }
+        };
+        if let Some(compression) = &context.compression {
+            response = match response
+                .compressed(accept_encoding.as_deref(), compression)
+                .await
+            {
+                Ok(response) => response,
+                Err(e) => {
+                    eprintln!("{e}");
+                    return;
+                }
+            };
+        }
+        if keep_alive {
+            response.add_header("Connection", "keep-alive");
+            response.add_header(
+                "Keep-Alive",
+                format!(
+                    "timeout={}, max={}",
+                    idle_timeout.as_secs(),
+                    max_requests - served
+                ),
+            );
+        } else {
+            response.add_header("Connection", "close");
+        }
+        println!("{response:?}");
+
+        if let Err(e) = response.write_to(&mut stream).await {
+            eprintln!("{e}");
+            return;
+        }
+        if !keep_alive {
+            return;
+        }
+    }
+}
+
+// Answers a request whose head or body could not be read. The connection
+// is closed afterwards, there is no telling where the next request starts.
+async fn reject<S: AsyncWrite + Unpin>(stream: &mut S, e: anyhow::Error) {
+    let status = match parse_error(&e) {
+        Some(RequestParseError::ConnectionClosed) => return,
+        Some(RequestParseError::BodyTooLarge(_)) => HttpStatus::ContentTooLarge,
+        Some(RequestParseError::HeaderTimeout(_) | RequestParseError::BodyTimeout(_)) => {
+            HttpStatus::RequestTimeout
+        }
+        _ => {
             eprintln!("{e}");
             return;
         }
     };
+    let mut response = Response::new(
+        status,
+        &ContentType::PlainText,
+        response::body::Body::RawText(e.to_string()),
+    );
+    response.add_header("Connection", "close");
+    if let Err(e) = response.write_to(stream).await {
+        eprintln!("{e}");
+    }
+}
+
+// a handler streaming the body may hand back the `io::Error` it got from
+// `BodyStream` as is
+fn parse_error(e: &anyhow::Error) -> Option<&RequestParseError> {
+    e.downcast_ref().or_else(|| {
+        e.downcast_ref::<io::Error>()
+            .and_then(|e| e.get_ref())
+            .and_then(|e| e.downcast_ref())
+    })
+}
 
+// `Err` only when the request body could not be read, other handler errors
+// become a 500
+async fn respond(
+    request: &mut StreamingRequest<'_>,
+    context: &AppContext,
+) -> anyhow::Result<Response> {
     let abs_path = request.header().path.abs_path();
     println!("abs_path: {abs_path}");
-    let response = if abs_path.starts_with("/static/") {
-        *request.header_mut().path.abs_path_mut() = abs_path[7..].to_string();
-        match context.static_router.route(&mut request) {
-            Some(handler) => match handler.handle(&request).await {
-                Ok(r) => r,
-                Err(e) => Response::new(
-                    HttpStatus::InternalServerError,
-                    &ContentType::PlainText,
-                    response::body::Body::RawText(format!("Internal Server Error: {e}")),
-                ),
-            },
-            None => Response::new(
-                HttpStatus::NotFound,
+    if !abs_path.starts_with("/static/") {
+        return Ok(Response::new(
+            HttpStatus::NotFound,
+            &ContentType::PlainText,
+            response::body::Body::RawText("Not Found".to_string()),
+        ));
+    }
+    *request.header_mut().path.abs_path_mut() = abs_path[7..].to_string();
+    Ok(match context.static_router.route(request) {
+        Some(handler) => match handler.handle_stream(request).await {
+            Ok(r) => r,
+            Err(e) if parse_error(&e).is_some() => return Err(e),
+            Err(e) => Response::new(
+                HttpStatus::InternalServerError,
                 &ContentType::PlainText,
-                response::body::Body::RawText("Not Found".to_string()),
+                response::body::Body::RawText(format!("Internal Server Error: {e}")),
             ),
-        }
-    } else {
-        Response::new(
+        },
+        None => Response::new(
             HttpStatus::NotFound,
             &ContentType::PlainText,
             response::body::Body::RawText("Not Found".to_string()),
-        )
-    };
-    println!("{response:?}");
+        ),
+    })
+}
 
-    if let Err(e) = stream.write_all(&response.serialize()).await {
-        eprintln!("{e}");
+#[cfg(test)]
+mod test {
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    async fn exchange(context: AppContext, raw: &str) -> String {
+        let (mut client, server) = duplex(64 * 1024);
+        let serve = tokio::spawn(handle_request(server, Arc::new(context)));
+        client.write_all(raw.as_bytes()).await.unwrap();
+        let mut out = String::new();
+        client.read_to_string(&mut out).await.unwrap();
+        serve.await.unwrap();
+        out
+    }
+
+    // body of a chunked response, decoded
+    fn dechunk(response: &str) -> String {
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        let mut decoder = request::chunked::ChunkedDecoder::new();
+        let mut out = vec![0; body.len()];
+        let (_, written) = decoder.decode(body.as_bytes(), &mut out).unwrap();
+        assert!(decoder.is_done());
+        String::from_utf8(out[..written].to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_keep_alive() {
+        let raw = "GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\n\r\nGET /c HTTP/1.1\r\nConnection: close\r\n\r\n";
+        let out = exchange(AppContext::new(), raw).await;
+        assert_eq!(out.matches("HTTP/1.1 404 Not Found").count(), 3);
+        assert_eq!(out.matches("Connection: keep-alive").count(), 2);
+        assert!(out.contains("Keep-Alive: timeout=5, max=98"));
+        assert_eq!(out.matches("Connection: close").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_http_1_0_closes_by_default() {
+        let raw = "GET /a HTTP/1.0\r\n\r\nGET /b HTTP/1.1\r\n\r\n";
+        let out = exchange(AppContext::new(), raw).await;
+        assert_eq!(out.matches("HTTP/1.1 404").count(), 1);
+        assert!(out.contains("Connection: close"));
+
+        let raw = "GET /a HTTP/1.0\r\nConnection: keep-alive\r\n\r\n";
+        let context = AppContext::new().with_connection_config(ConnectionConfig {
+            idle_timeout: Duration::from_millis(50),
+            ..Default::default()
+        });
+        let out = exchange(context, raw).await;
+        assert!(out.contains("Connection: keep-alive"));
+    }
+
+    #[tokio::test]
+    async fn test_max_requests_per_connection() {
+        let context = AppContext::new().with_connection_config(ConnectionConfig {
+            max_requests: 2,
+            ..Default::default()
+        });
+        let raw = "GET /a HTTP/1.1\r\n\r\n".repeat(3);
+        let out = exchange(context, &raw).await;
+        assert_eq!(out.matches("HTTP/1.1 404").count(), 2);
+        assert!(out.contains("Keep-Alive: timeout=5, max=1"));
+        assert!(out.ends_with("Not Found"));
+        assert_eq!(out.matches("Connection: close").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_zero_max_requests() {
+        let context = AppContext::new().with_connection_config(ConnectionConfig {
+            max_requests: 0,
+            ..Default::default()
+        });
+        let out = exchange(context, &"GET /a HTTP/1.1\r\n\r\n".repeat(2)).await;
+        assert_eq!(out.matches("HTTP/1.1 404").count(), 1);
+        assert!(out.contains("Connection: close"));
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout_spares_slow_body() {
+        let context = AppContext::new().with_connection_config(ConnectionConfig {
+            idle_timeout: Duration::from_millis(50),
+            ..Default::default()
+        });
+        let (mut client, server) = duplex(64 * 1024);
+        let serve = tokio::spawn(handle_request(server, Arc::new(context)));
+        client
+            .write_all(b"POST /upload HTTP/1.1\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhe")
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        client.write_all(b"llo").await.unwrap();
+        let mut out = String::new();
+        client.read_to_string(&mut out).await.unwrap();
+        serve.await.unwrap();
+        assert!(out.starts_with("HTTP/1.1 404"), "{out}");
+
+        // nothing at all within the idle timeout closes the connection
+        let context = AppContext::new().with_connection_config(ConnectionConfig {
+            idle_timeout: Duration::from_millis(50),
+            ..Default::default()
+        });
+        let (mut client, server) = duplex(64 * 1024);
+        let serve = tokio::spawn(handle_request(server, Arc::new(context)));
+        let mut out = String::new();
+        client.read_to_string(&mut out).await.unwrap();
+        serve.await.unwrap();
+        assert!(out.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_body_timeout() {
+        // skipped by the server after a 404, or streamed by a handler
+        for (path, context) in [
+            ("/a", AppContext::new()),
+            ("/static/upload", upload_context(1024, 1024)),
+        ] {
+            let context = context.with_connection_config(ConnectionConfig {
+                body_timeout: Duration::from_millis(50),
+                ..Default::default()
+            });
+            let raw = format!("POST {path} HTTP/1.1\r\nContent-Length: 5\r\n\r\nhe");
+            let out = exchange(context, &raw).await;
+            assert!(out.contains("HTTP/1.1 408 Request Timeout"), "{out}");
+            assert!(out.contains("Connection: close"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_header_timeout() {
+        let config = ConnectionConfig {
+            header_timeout: Duration::from_millis(50),
+            ..Default::default()
+        };
+        // half a head and then silence, the client keeps the connection open
+        let context = AppContext::new().with_connection_config(config);
+        let out = exchange(context, "GET /a HTTP/1.1\r\nHost: exa").await;
+        assert!(out.starts_with("HTTP/1.1 408 Request Timeout"), "{out}");
+        assert!(out.contains("Connection: close"));
+
+        // same for a partial request pipelined after a complete one
+        let context = AppContext::new().with_connection_config(config);
+        let out = exchange(context, "GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\n").await;
+        assert!(out.starts_with("HTTP/1.1 404 Not Found"), "{out}");
+        assert_eq!(out.matches("HTTP/1.1 408 Request Timeout").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_static_file_streamed() {
+        let raw = "GET /static/index.html HTTP/1.1\r\nConnection: close\r\n\r\n";
+        let out = exchange(AppContext::new(), raw).await;
+        let index = std::fs::read_to_string("html/index.html").unwrap();
+        assert!(out.starts_with("HTTP/1.1 200 OK"));
+        assert!(out.contains(&format!("Content-Length: {}", index.len())));
+        assert!(out.ends_with(&index));
+    }
+
+    #[tokio::test]
+    async fn test_static_range_requests() {
+        let index = std::fs::read_to_string("html/index.html").unwrap();
+        let len = index.len();
+        let raw =
+            "GET /static/index.html HTTP/1.1\r\nRange: bytes=0-9\r\nConnection: close\r\n\r\n";
+        let out = exchange(AppContext::new(), raw).await;
+        assert!(out.starts_with("HTTP/1.1 206 Partial Content"));
+        assert!(out.contains(&format!("Content-Range: bytes 0-9/{len}")));
+        assert!(out.contains("Content-Length: 10"));
+        assert!(out.contains("Accept-Ranges: bytes"));
+        assert!(out.ends_with(&index[..10]));
+
+        let raw =
+            "GET /static/index.html HTTP/1.1\r\nRange: bytes=0-4, -5\r\nConnection: close\r\n\r\n";
+        let out = exchange(AppContext::new(), raw).await;
+        assert!(out.starts_with("HTTP/1.1 206 Partial Content"));
+        assert!(out.contains("Content-Type: multipart/byteranges; boundary=black-hawk-"));
+        let out = dechunk(&out);
+        assert!(out.contains(&format!(
+            "Content-Range: bytes 0-4/{len}\r\n\r\n{}\r\n",
+            &index[..5]
+        )));
+        assert!(out.contains(&format!(
+            "Content-Range: bytes {}-{}/{len}\r\n\r\n{}\r\n",
+            len - 5,
+            len - 1,
+            &index[len - 5..]
+        )));
+        assert!(out.starts_with("--black-hawk-"));
+        assert!(out.ends_with("--\r\n"));
+
+        let raw = format!(
+            "GET /static/index.html HTTP/1.1\r\nRange: bytes={len}-\r\nConnection: close\r\n\r\n"
+        );
+        let out = exchange(AppContext::new(), &raw).await;
+        assert!(out.starts_with("HTTP/1.1 416 Range Not Satisfiable"));
+        assert!(out.contains(&format!("Content-Range: bytes */{len}")));
+
+        // malformed ranges are ignored
+        let raw =
+            "GET /static/index.html HTTP/1.1\r\nRange: bytes=9-1\r\nConnection: close\r\n\r\n";
+        let out = exchange(AppContext::new(), raw).await;
+        assert!(out.starts_with("HTTP/1.1 200 OK"));
+        assert!(out.ends_with(&index));
+    }
+
+    fn header<'r>(response: &'r str, name: &str) -> &'r str {
+        let prefix = format!("\r\n{name}: ");
+        let start = response.find(&prefix).unwrap() + prefix.len();
+        let len = response[start..].find("\r\n").unwrap();
+        &response[start..start + len]
+    }
+
+    #[tokio::test]
+    async fn test_static_conditional_requests() {
+        let raw = "GET /static/index.html HTTP/1.1\r\nConnection: close\r\n\r\n";
+        let out = exchange(AppContext::new(), raw).await;
+        let etag = header(&out, "ETag").to_string();
+        let last_modified = header(&out, "Last-Modified").to_string();
+        assert_eq!(header(&out, "Cache-Control"), "no-cache");
+        assert!(date::parse_http_date(&last_modified).is_some());
+
+        for condition in [
+            format!("If-None-Match: \"nope\", {etag}"),
+            format!("If-None-Match: W/{etag}"),
+            "If-None-Match: *".to_string(),
+            format!("If-Modified-Since: {last_modified}"),
+        ] {
+            let raw = format!(
+                "GET /static/index.html HTTP/1.1\r\n{condition}\r\nConnection: close\r\n\r\n"
+            );
+            let out = exchange(AppContext::new(), &raw).await;
+            assert!(out.starts_with("HTTP/1.1 304 Not Modified"), "{condition}");
+            assert_eq!(header(&out, "ETag"), etag);
+            assert!(!out.contains("Content-Length"));
+            assert!(out.ends_with("\r\n\r\n"));
+        }
+
+        for condition in [
+            "If-None-Match: \"nope\"",
+            "If-Modified-Since: Thu, 01 Jan 1970 00:00:00 GMT",
+            "If-Modified-Since: garbage",
+        ] {
+            let raw = format!(
+                "GET /static/index.html HTTP/1.1\r\n{condition}\r\nConnection: close\r\n\r\n"
+            );
+            let out = exchange(AppContext::new(), &raw).await;
+            assert!(out.starts_with("HTTP/1.1 200 OK"), "{condition}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cache_control_per_extension() {
+        let handler = StaticHandler::new()
+            .with_cache_control("png", "public, max-age=31536000, immutable")
+            .with_default_cache_control(None);
+        let context = AppContext::new().with_static_handler(handler);
+        let raw = "GET /static/images/logo.png HTTP/1.1\r\nRange: bytes=1-3\r\n\r\nGET /static/index.html HTTP/1.1\r\nConnection: close\r\n\r\n";
+        let out = exchange(context, raw).await;
+        let (png, html) = out.split_at(out.rfind("HTTP/1.1 200").unwrap());
+        assert!(png.starts_with("HTTP/1.1 206") && png.ends_with("PNG"));
+        assert_eq!(
+            header(png, "Cache-Control"),
+            "public, max-age=31536000, immutable"
+        );
+        assert_eq!(header(html, "Cache-Control"), "no-cache");
+    }
+
+    async fn get_status(path: &str) -> String {
+        let raw = format!("GET {path} HTTP/1.1\r\nConnection: close\r\n\r\n");
+        let out = exchange(AppContext::new(), &raw).await;
+        out.lines().next().unwrap_or_default().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_static_path_traversal() {
+        for payload in [
+            "/static/../Cargo.toml",
+            "/static/../../../../etc/passwd",
+            "/static/images/../../Cargo.toml",
+            "/static/%2e%2e/Cargo.toml",
+            "/static/%2E%2E/%2E%2E/etc/passwd",
+            "/static/.%2e/Cargo.toml",
+            "/static/..%2fCargo.toml",
+            "/static/%2e%2e%2fCargo.toml",
+            "/static/images%2f..%2f..%2fCargo.toml",
+            "/static/..%5cCargo.toml",
+            "/static/..\\Cargo.toml",
+            "/static/index.html%00.png",
+            "/static/%00",
+            "/static/%c0%ae%c0%ae/Cargo.toml",
+        ] {
+            assert_eq!(
+                get_status(payload).await,
+                "HTTP/1.1 403 Forbidden",
+                "{payload}"
+            );
+        }
+        for (path, status) in [
+            ("/static/./index.html", "HTTP/1.1 200 OK"),
+            ("/static/images/../index.html", "HTTP/1.1 200 OK"),
+            ("/static//index.html", "HTTP/1.1 200 OK"),
+            ("/static/%69ndex.html", "HTTP/1.1 200 OK"),
+            ("/static/images/..", "HTTP/1.1 200 OK"),
+            ("/static/missing.html", "HTTP/1.1 404 Not Found"),
+        ] {
+            assert_eq!(get_status(path).await, status, "{path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_static_compression() {
+        let context = || {
+            AppContext::new().with_compression(Some(CompressionConfig {
+                min_size: 64,
+                streaming: true,
+            }))
+        };
+        let index = std::fs::read_to_string("html/index.html").unwrap();
+        let raw = "GET /static/index.html HTTP/1.1\r\nAccept-Encoding: gzip;q=0.8, br;q=0.9\r\nConnection: close\r\n\r\n";
+        let (mut client, server) = duplex(64 * 1024);
+        let serve = tokio::spawn(handle_request(server, Arc::new(context())));
+        client.write_all(raw.as_bytes()).await.unwrap();
+        let mut out = vec![];
+        client.read_to_end(&mut out).await.unwrap();
+        serve.await.unwrap();
+        let split = out.windows(4).position(|w| w == DELIMITER).unwrap() + 4;
+        let head = String::from_utf8_lossy(&out[..split]).to_string();
+        assert_eq!(header(&head, "Content-Encoding"), "br");
+        assert_eq!(header(&head, "Vary"), "Accept-Encoding");
+        assert_eq!(header(&head, "Transfer-Encoding"), "chunked");
+        assert!(header(&head, "ETag").starts_with("W/\""));
+        let mut body = vec![];
+        let mut decoder = request::chunked::ChunkedDecoder::new();
+        let mut buf = vec![0; out.len()];
+        let (_, written) = decoder.decode(&out[split..], &mut buf).unwrap();
+        assert!(decoder.is_done());
+        async_compression::tokio::bufread::BrotliDecoder::new(&buf[..written])
+            .read_to_end(&mut body)
+            .await
+            .unwrap();
+        assert_eq!(body, index.as_bytes());
+
+        // no Accept-Encoding, identity body but still `Vary`
+        let raw = "GET /static/index.html HTTP/1.1\r\nConnection: close\r\n\r\n";
+        let out = exchange(context(), raw).await;
+        assert!(!out.contains("Content-Encoding"));
+        assert_eq!(header(&out, "Vary"), "Accept-Encoding");
+        assert!(out.ends_with(&index));
+
+        // ranges and images are never compressed
+        for raw in [
+            "GET /static/index.html HTTP/1.1\r\nAccept-Encoding: gzip\r\nRange: bytes=0-9\r\nConnection: close\r\n\r\n",
+            "GET /static/images/logo.png HTTP/1.1\r\nAccept-Encoding: gzip\r\nRange: bytes=1-3\r\nConnection: close\r\n\r\n",
+        ] {
+            let out = exchange(context(), raw).await;
+            assert!(!out.contains("Content-Encoding"), "{raw}");
+            assert!(!out.contains("Vary"), "{raw}");
+        }
+
+        // below the default threshold
+        let raw =
+            "GET /static/index.html HTTP/1.1\r\nAccept-Encoding: gzip\r\nConnection: close\r\n\r\n";
+        let out = exchange(AppContext::new(), raw).await;
+        assert!(!out.contains("Content-Encoding"));
+    }
+
+    #[tokio::test]
+    async fn test_body_too_large() {
+        let context = AppContext::new().with_connection_config(ConnectionConfig {
+            max_body_size: 4,
+            ..Default::default()
+        });
+        let raw = "POST /a HTTP/1.1\r\nContent-Length: 5\r\n\r\nhelloGET /b HTTP/1.1\r\n\r\n";
+        let out = exchange(context, raw).await;
+        assert!(out.starts_with("HTTP/1.1 413 Content Too Large"));
+        assert!(out.contains("Connection: close"));
+        assert!(!out.contains("404"));
+    }
+
+    // reads at most `limit` bytes of the body and reports how many it got
+    struct Upload {
+        limit: u64,
+    }
+
+    #[async_trait::async_trait]
+    impl handler::Handler for Upload {
+        async fn handle(&self, _request: &request::HttpRequest) -> anyhow::Result<Response> {
+            unreachable!("uploads are streamed")
+        }
+
+        async fn handle_stream(
+            &self,
+            request: &mut StreamingRequest<'_>,
+        ) -> anyhow::Result<Response> {
+            let mut body = request.body_stream().take(self.limit);
+            let n = tokio::io::copy(&mut body, &mut tokio::io::sink()).await?;
+            Ok(Response::new(
+                HttpStatus::Ok,
+                &ContentType::PlainText,
+                response::body::Body::RawText(format!("read {n}")),
+            ))
+        }
+    }
+
+    fn upload_context(limit: u64, max_body_size: usize) -> AppContext {
+        let mut context = AppContext::new().with_connection_config(ConnectionConfig {
+            max_body_size,
+            ..Default::default()
+        });
+        context
+            .static_router
+            .add_route(&HttpMethod::Post, "/upload", Box::new(Upload { limit }));
+        context
+    }
+
+    #[tokio::test]
+    async fn test_streaming_handler() {
+        // the unread rest of the body is skipped, not taken for a request
+        let raw = "POST /static/upload HTTP/1.1\r\nContent-Length: 11\r\n\r\nhello world\
+                   GET /a HTTP/1.1\r\nConnection: close\r\n\r\n";
+        let out = exchange(upload_context(5, 1024), raw).await;
+        assert!(out.starts_with("HTTP/1.1 200 OK"));
+        assert!(out.contains("read 5"));
+        assert_eq!(out.matches("HTTP/1.1 404 Not Found").count(), 1);
+
+        let raw = "POST /static/upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+                   6\r\nhello \r\n5\r\nworld\r\n0\r\n\r\n\
+                   GET /a HTTP/1.1\r\nConnection: close\r\n\r\n";
+        let out = exchange(upload_context(1024, 1024), raw).await;
+        assert!(out.contains("read 11"));
+        assert_eq!(out.matches("HTTP/1.1 404 Not Found").count(), 1);
+
+        // over the limit while the handler streams it
+        let raw = "POST /static/upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+                   b\r\nhello world\r\n0\r\n\r\n";
+        let out = exchange(upload_context(1024, 4), raw).await;
+        assert!(out.starts_with("HTTP/1.1 413 Content Too Large"));
+        assert!(out.contains("Connection: close"));
     }
 }
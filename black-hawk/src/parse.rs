@@ -12,6 +12,7 @@ use crate::{
     error::RequestParseError,
     request::{
         boundary::Boundary,
+        chunked::ChunkedDecoder,
         path::{HttpPath, Query},
         HttpHeaders, HttpMethod, HttpRequestHeader, HttpVersion, RequestBody,
     },
@@ -108,33 +109,19 @@ pub(crate) fn parse_http_header(i: &[u8]) -> Result<HttpRequestHeader, RequestPa
     })
 }
 
+// decodes a complete chunked body already held in memory
 pub(crate) fn parse_chunked_body(i: &[u8]) -> Result<RequestBody, RequestParseError> {
-    let chunked_lines = String::from_utf8_lossy(i);
-    let mut all_contents = String::new();
-    let mut chunk_sizes = vec![];
-    let mut c_lines = chunked_lines.lines();
-    while let Some(line) = c_lines.next() {
-        if line.trim().is_empty() {
-            continue;
-        }
-        let chunk_size = usize::from_str_radix(line.trim(), 16)?;
-        if chunk_size == 0 {
-            break;
-        }
-        let line = c_lines.next().ok_or(RequestParseError::ParseChunkContent)?;
-        if line.len() != chunk_size {
-            return Err(RequestParseError::ChunkContentLengthUnmatch(
-                chunk_size,
-                line.len(),
-            ));
-        }
-        chunk_sizes.push(chunk_size);
-        all_contents.push_str(line);
+    let mut decoder = ChunkedDecoder::new();
+    let mut content = vec![0; i.len()];
+    let (consumed, written) = decoder.decode(i, &mut content)?;
+    if !decoder.is_done() {
+        return Err(RequestParseError::IncompleteChunkedBody);
     }
-    Ok(RequestBody::Chunked {
-        content: all_contents,
-        sizes: chunk_sizes,
-    })
+    if consumed != i.len() {
+        return Err(RequestParseError::ParseChunkContent);
+    }
+    content.truncate(written);
+    Ok(decoder.into_body(content))
 }
 
 pub(crate) fn parse_multipart_boundary(
@@ -335,4 +322,19 @@ mod test {
             "keep-alive"
         );
     }
+
+    #[test]
+    fn test_parse_chunked_body() {
+        let body = parse_chunked_body(b"3\r\na\r\n\r\n0\r\n\r\n").unwrap();
+        assert_eq!(
+            body,
+            RequestBody::Chunked {
+                content: b"a\r\n".to_vec(),
+                sizes: vec![3],
+                trailers: HttpHeaders::default(),
+            }
+        );
+        assert!(parse_chunked_body(b"3\r\na\r\n\r\n").is_err());
+        assert!(parse_chunked_body(b"0\r\n\r\nextra").is_err());
+    }
 }
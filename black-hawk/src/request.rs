@@ -1,11 +1,11 @@
 use std::{collections::HashMap, fmt::Display, time::Duration};
 
+use body::{unwrap_io_error, BodyStream, Framing};
 use boundary::Boundary;
+use chunked::ChunkedDecoder;
 use path::HttpPath;
-use tokio::{
-    io::{AsyncRead, AsyncReadExt},
-    time::timeout,
-};
+use range::{parse_range_header, ByteRange};
+use tokio::io::{AsyncRead, AsyncReadExt};
 
 use crate::{
     consts::{ContentType, TransferEncoding},
@@ -13,18 +13,21 @@ use crate::{
     parse::{parse_http_header, parse_request_body},
 };
 
+pub mod body;
 pub mod boundary;
+pub mod chunked;
 pub mod path;
+pub mod range;
 
 #[derive(Debug)]
 #[allow(unused)]
-pub struct HttpRequest {
+pub struct HttpRequest<B = RequestBody> {
     pub(crate) header: HttpRequestHeader,
-    pub(crate) body: RequestBody,
+    pub(crate) body: B,
     pub(crate) path_vars: HashMap<String, String>,
 }
 
-impl HttpRequest {
+impl<B> HttpRequest<B> {
     pub fn header(&self) -> &HttpRequestHeader {
         &self.header
     }
@@ -34,7 +37,7 @@ impl HttpRequest {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct HttpHeaders(pub(crate) HashMap<String, String>);
 
 impl From<HashMap<String, String>> for HttpHeaders {
@@ -43,7 +46,7 @@ impl From<HashMap<String, String>> for HttpHeaders {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[allow(unused)]
 pub struct HttpRequestHeader {
     pub(crate) method: HttpMethod,
@@ -72,6 +75,31 @@ impl HttpRequestHeader {
     pub fn accept(&self) -> Option<&str> {
         self.headers.0.get("Accept").map(|ac| ac.as_str())
     }
+
+    // `None` when there is no `Range` header or it is malformed, which
+    // means the whole representation should be sent
+    pub fn range(&self) -> Option<Vec<ByteRange>> {
+        parse_range_header(self.headers.0.get("Range")?)
+    }
+
+    // `Connection: close`/`keep-alive` wins, otherwise HTTP/1.1 and later
+    // keep the connection open and HTTP/1.0 closes it
+    pub fn keep_alive(&self) -> bool {
+        let connection = self
+            .headers
+            .0
+            .get("Connection")
+            .map(|c| c.to_ascii_lowercase())
+            .unwrap_or_default();
+        let mut tokens = connection.split(',').map(str::trim);
+        if tokens.clone().any(|t| t == "close") {
+            false
+        } else if tokens.any(|t| t == "keep-alive") {
+            true
+        } else {
+            self.version != HttpVersion::V1_0
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -110,65 +138,74 @@ impl Display for HttpVersion {
 pub enum RequestBody {
     Nil,
     RawText(String),
-    Chunked { content: String, sizes: Vec<usize> },
+    Chunked {
+        content: Vec<u8>,
+        sizes: Vec<usize>,
+        trailers: HttpHeaders,
+    },
     MultiPart(Vec<Boundary>),
     Json(serde_json::Value),
 }
 
 pub async fn read_http_request<R: AsyncRead + Unpin>(mut stream: R) -> anyhow::Result<HttpRequest> {
+    let body_timeout = crate::ConnectionConfig::default().body_timeout;
+    read_pipelined_request(&mut stream, &mut vec![], usize::MAX, body_timeout).await
+}
+
+// `pending` carries bytes read past the end of the previous request on the
+// same connection, and is left holding whatever follows this one, so
+// pipelined requests are parsed in order without losing data
+pub async fn read_pipelined_request<R: AsyncRead + Unpin>(
+    stream: &mut R,
+    pending: &mut Vec<u8>,
+    max_body_size: usize,
+    body_timeout: Duration,
+) -> anyhow::Result<HttpRequest> {
+    read_streaming_request(stream, pending, max_body_size, body_timeout)
+        .await?
+        .into_buffered()
+        .await
+}
+
+// Reads only the request head; the body is left on the connection behind a
+// `BodyStream`, so a handler can copy it somewhere without buffering it.
+pub async fn read_streaming_request<'a, R: AsyncRead + Unpin + ?Sized>(
+    stream: &'a mut R,
+    pending: &'a mut Vec<u8>,
+    max_body_size: usize,
+    body_timeout: Duration,
+) -> anyhow::Result<HttpRequest<BodyStream<'a, R>>> {
     let mut buf = [0; 4096];
-    let mut header_buf = vec![];
-    let mut body_buf = vec![];
-    loop {
+    let h_len = loop {
+        if let Some(h_len) = pending.windows(4).position(|w| w == super::DELIMITER) {
+            break h_len;
+        }
         let n = stream.read(&mut buf).await?;
-        if let Some(h_len) = buf[..n].windows(4).position(|w| w == super::DELIMITER) {
-            header_buf.extend_from_slice(&buf[..h_len]);
-            body_buf.extend_from_slice(&buf[h_len + 4..n]);
-            break;
+        if n == 0 {
+            return Err(if pending.is_empty() {
+                RequestParseError::ConnectionClosed
+            } else {
+                RequestParseError::IncompleteHeader
+            }
+            .into());
         }
-        header_buf.extend_from_slice(&buf[..n]);
-    }
-    // println!("{}", String::from_utf8_lossy(&header_buf));
-    let header = parse_http_header(&header_buf)?;
-    let content_length = header.content_length().unwrap_or(0);
-    let mut remaining = content_length.saturating_sub(body_buf.len());
-    let body = if let Some(ContentType::MultiPart(bound)) = header.content_type() {
+        pending.extend_from_slice(&buf[..n]);
+    };
+    // println!("{}", String::from_utf8_lossy(&pending[..h_len]));
+    // keep the CRLF that ends the request line when there are no headers
+    let header = parse_http_header(&pending[..h_len + 2])?;
+    pending.drain(..h_len + 4);
+    let framing = if let Some(TransferEncoding::Chunked) = header.transfer_encoding() {
+        Framing::Chunked(ChunkedDecoder::new())
+    } else if let Some(ContentType::MultiPart(bound)) = header.content_type() {
         let mut boundary = String::new();
         boundary.push_str(bound.as_str());
         boundary.push_str("--\r\n\r\n");
-        while !body_buf.ends_with(boundary.as_bytes()) {
-            let n = timeout(Duration::from_secs(5), stream.read(&mut buf)).await??;
-            if n == 0 {
-                return Err(RequestParseError::IncompleteMultipartBody.into());
-            }
-            body_buf.extend_from_slice(&buf[..n]);
-        }
-        body_buf
-    } else if let Some(TransferEncoding::Chunked) = header.transfer_encoding() {
-        loop {
-            if body_buf.ends_with(b"0\r\n\r\n") {
-                break body_buf;
-            }
-            let n = timeout(Duration::from_secs(5), stream.read(&mut buf)).await??;
-            if n == 0 {
-                return Err(RequestParseError::IncompleteChunkedBody.into());
-            }
-            body_buf.extend_from_slice(&buf[..n]);
-        }
+        Framing::Until(boundary.into_bytes())
     } else {
-        while remaining > 0 {
-            let n = timeout(Duration::from_secs(5), stream.read(&mut buf)).await??;
-            body_buf.extend_from_slice(&buf[..n]);
-            remaining -= n;
-        }
-        body_buf
-    };
-
-    let body = if body.is_empty() {
-        RequestBody::Nil
-    } else {
-        parse_request_body(&body, &header)?
+        Framing::Length(header.content_length().unwrap_or(0))
     };
+    let body = BodyStream::new(stream, pending, framing, max_body_size, body_timeout)?;
     Ok(HttpRequest {
         header,
         body,
@@ -176,6 +213,65 @@ pub async fn read_http_request<R: AsyncRead + Unpin>(mut stream: R) -> anyhow::R
     })
 }
 
+// what `Handler::handle_stream` gets, whatever the connection type
+pub type StreamingRequest<'a> = HttpRequest<BodyStream<'a, dyn AsyncRead + Unpin + Send + 'a>>;
+
+impl<'a, R: AsyncRead + Unpin + ?Sized> HttpRequest<BodyStream<'a, R>> {
+    pub fn body_stream(&mut self) -> &mut BodyStream<'a, R> {
+        &mut self.body
+    }
+
+    // reads the rest of the body and parses it like `read_http_request` does
+    pub async fn into_buffered(mut self) -> anyhow::Result<HttpRequest> {
+        let body = self.read_body().await?;
+        Ok(HttpRequest {
+            header: self.header,
+            body,
+            path_vars: self.path_vars,
+        })
+    }
+
+    // same, but leaves the drained stream in place
+    pub async fn to_buffered(&mut self) -> anyhow::Result<HttpRequest> {
+        let body = self.read_body().await?;
+        Ok(HttpRequest {
+            header: self.header.clone(),
+            body,
+            path_vars: self.path_vars.clone(),
+        })
+    }
+
+    // throws away whatever part of the body is still unread
+    pub(crate) async fn skip_body(&mut self) -> anyhow::Result<()> {
+        let mut buf = [0; 4096];
+        while self.read_body_chunk(&mut buf).await? > 0 {}
+        Ok(())
+    }
+
+    async fn read_body(&mut self) -> anyhow::Result<RequestBody> {
+        let mut buf = [0; 4096];
+        let mut raw = vec![];
+        loop {
+            let n = self.read_body_chunk(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            raw.extend_from_slice(&buf[..n]);
+        }
+        Ok(if let Some(decoder) = self.body.take_chunked_decoder() {
+            decoder.into_body(raw)
+        } else if raw.is_empty() {
+            RequestBody::Nil
+        } else {
+            parse_request_body(&raw, &self.header)?
+        })
+    }
+
+    async fn read_body_chunk(&mut self, buf: &mut [u8]) -> anyhow::Result<usize> {
+        self.body.read(buf).await.map_err(unwrap_io_error)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::request::path::Query;
@@ -217,8 +313,9 @@ mod test {
         assert_eq!(
             request.body,
             RequestBody::Chunked {
-                content: "all your base are belong to us".to_string(),
+                content: b"all your base are belong to us".to_vec(),
                 sizes: vec![0x1e],
+                trailers: HttpHeaders::default(),
             }
         );
     }
@@ -230,12 +327,71 @@ mod test {
         assert_eq!(
             request.body,
             RequestBody::Chunked {
-                content: "hello world".to_string(),
-                sizes: vec![5, 6]
+                content: b"hello world".to_vec(),
+                sizes: vec![5, 6],
+                trailers: HttpHeaders::default(),
             }
         )
     }
 
+    #[tokio::test]
+    async fn test_read_pipelined_requests() {
+        let raw = "POST /first HTTP/1.1\r\nContent-Length: 5\r\n\r\nWorldGET /second HTTP/1.0\r\n\r\nPOST /third HTTP/1.1\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n5\r\nhello\r\n0\r\n\r\n";
+        let mut stream = raw.as_bytes();
+        let mut pending = vec![];
+        let first = read_pipelined_request(&mut stream, &mut pending, 1024, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(first.header.path.abs_path(), "/first");
+        assert_eq!(first.body, RequestBody::RawText("World".to_string()));
+        assert!(first.header.keep_alive());
+        let second =
+            read_pipelined_request(&mut stream, &mut pending, 1024, Duration::from_secs(5))
+                .await
+                .unwrap();
+        assert_eq!(second.header.path.abs_path(), "/second");
+        assert_eq!(second.body, RequestBody::Nil);
+        assert!(!second.header.keep_alive());
+        let third = read_pipelined_request(&mut stream, &mut pending, 1024, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(third.header.path.abs_path(), "/third");
+        assert!(!third.header.keep_alive());
+        assert!(pending.is_empty());
+        let closed =
+            read_pipelined_request(&mut stream, &mut pending, 1024, Duration::from_secs(5))
+                .await
+                .unwrap_err();
+        assert!(matches!(
+            closed.downcast_ref(),
+            Some(RequestParseError::ConnectionClosed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_http_request_read_chunked_binary_with_trailers() {
+        let raw = b"POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n4;part=1\r\n0\r\n\r\r\n2\r\n\xff\x00\r\n0\r\nDigest: abc\r\n\r\nGET /next HTTP/1.1\r\n\r\n";
+        let mut stream = &raw[..];
+        let mut pending = vec![];
+        let request =
+            read_pipelined_request(&mut stream, &mut pending, 1024, Duration::from_secs(5))
+                .await
+                .unwrap();
+        let trailers = HttpHeaders(HashMap::from([("Digest".to_string(), "abc".to_string())]));
+        assert_eq!(
+            request.body,
+            RequestBody::Chunked {
+                content: b"0\r\n\r\xff\x00".to_vec(),
+                sizes: vec![4, 2],
+                trailers,
+            }
+        );
+        let next = read_pipelined_request(&mut stream, &mut pending, 1024, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(next.header.path.abs_path(), "/next");
+    }
+
     #[tokio::test]
     async fn test_http_request_read_post_with_multipart_formdata() {
         let raw = "POST /upload HTTP/1.1\r\nHost: example.com\r\nContent-Type: multipart/form-data; boundary=--WebKitFormBoundaryABC123\r\nContent-Length: 345\r\n\r\n----WebKitFormBoundaryABC123\r\nContent-Disposition: form-data; name=\"username\"\r\n\r\nJohnDoe\r\n----WebKitFormBoundaryABC123\r\nContent-Disposition: form-data; name=\"file\"; filename=\"example.txt\"\r\nContent-Type: text/plain\r\n\r\nThis is the content of the file.\r\n----WebKitFormBoundaryABC123--\r\n\r\n";
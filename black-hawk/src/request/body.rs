@@ -0,0 +1,260 @@
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncRead, ReadBuf},
+    time::{sleep, Sleep},
+};
+
+use super::chunked::ChunkedDecoder;
+use crate::error::RequestParseError;
+
+#[derive(Debug)]
+pub(crate) enum Framing {
+    Length(usize),
+    Chunked(ChunkedDecoder),
+    // multipart bodies sent without a usable length end at the closing boundary
+    Until(Vec<u8>),
+}
+
+// The body of one request, read straight off the connection. Framing is
+// decoded as bytes arrive, so reading stops exactly at the end of the body
+// and anything pipelined after it stays in `pending` for the next request.
+#[derive(Debug)]
+pub struct BodyStream<'a, R: ?Sized> {
+    source: &'a mut R,
+    pending: &'a mut Vec<u8>,
+    framing: Framing,
+    max_size: usize,
+    read: usize,
+    done: bool,
+    // longest the connection may go quiet before the body is complete
+    read_timeout: Duration,
+    stall: Option<Pin<Box<Sleep>>>,
+}
+
+impl<'a, R: AsyncRead + Unpin + ?Sized> BodyStream<'a, R> {
+    pub(crate) fn new(
+        source: &'a mut R,
+        pending: &'a mut Vec<u8>,
+        framing: Framing,
+        max_size: usize,
+        read_timeout: Duration,
+    ) -> Result<Self, RequestParseError> {
+        if let Framing::Length(len) = framing {
+            if len > max_size {
+                return Err(RequestParseError::BodyTooLarge(max_size));
+            }
+        }
+        let done = matches!(framing, Framing::Length(0));
+        Ok(Self {
+            source,
+            pending,
+            framing,
+            max_size,
+            read: 0,
+            done,
+            read_timeout,
+            stall: None,
+        })
+    }
+
+    // decoded body bytes handed out so far
+    pub fn bytes_read(&self) -> usize {
+        self.read
+    }
+
+    pub fn is_chunked(&self) -> bool {
+        matches!(self.framing, Framing::Chunked(_))
+    }
+
+    // sizes, extensions and trailers seen so far on a chunked body
+    pub fn chunked_decoder(&self) -> Option<&ChunkedDecoder> {
+        match &self.framing {
+            Framing::Chunked(decoder) => Some(decoder),
+            _ => None,
+        }
+    }
+
+    // only once the body is done, the stream reads nothing after that
+    pub(crate) fn take_chunked_decoder(&mut self) -> Option<ChunkedDecoder> {
+        match std::mem::replace(&mut self.framing, Framing::Length(0)) {
+            Framing::Chunked(decoder) => Some(decoder),
+            framing => {
+                self.framing = framing;
+                None
+            }
+        }
+    }
+
+    fn decode_pending(&mut self, out: &mut [u8]) -> Result<usize, RequestParseError> {
+        let written = match &mut self.framing {
+            Framing::Length(remaining) => {
+                let n = (*remaining).min(self.pending.len()).min(out.len());
+                out[..n].copy_from_slice(&self.pending[..n]);
+                self.pending.drain(..n);
+                *remaining -= n;
+                self.done = *remaining == 0;
+                n
+            }
+            Framing::Chunked(decoder) => {
+                let (consumed, written) = decoder.decode(self.pending, out)?;
+                self.pending.drain(..consumed);
+                self.done = decoder.is_done();
+                written
+            }
+            Framing::Until(terminator) => {
+                if let Some(pos) = self
+                    .pending
+                    .windows(terminator.len())
+                    .position(|w| w == terminator.as_slice())
+                {
+                    self.framing = Framing::Length(pos + terminator.len());
+                    return self.decode_pending(out);
+                }
+                // hold back a tail that could be the start of the terminator
+                let safe = self.pending.len().saturating_sub(terminator.len() - 1);
+                let n = safe.min(out.len());
+                out[..n].copy_from_slice(&self.pending[..n]);
+                self.pending.drain(..n);
+                n
+            }
+        };
+        self.read += written;
+        if self.read > self.max_size {
+            return Err(RequestParseError::BodyTooLarge(self.max_size));
+        }
+        Ok(written)
+    }
+
+    fn incomplete(&self) -> RequestParseError {
+        match self.framing {
+            Framing::Length(_) => RequestParseError::IncompleteBody,
+            Framing::Chunked(_) => RequestParseError::IncompleteChunkedBody,
+            Framing::Until(_) => RequestParseError::IncompleteMultipartBody,
+        }
+    }
+}
+
+fn invalid_data(e: RequestParseError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+// recovers the parse error `BodyStream` wrapped into an `io::Error`
+pub(crate) fn unwrap_io_error(e: io::Error) -> anyhow::Error {
+    if !e
+        .get_ref()
+        .is_some_and(|inner| inner.is::<RequestParseError>())
+    {
+        return e.into();
+    }
+    match e
+        .into_inner()
+        .map(|inner| inner.downcast::<RequestParseError>())
+    {
+        Some(Ok(parse)) => (*parse).into(),
+        _ => unreachable!("checked the error type above"),
+    }
+}
+
+impl<R: AsyncRead + Unpin + ?Sized> AsyncRead for BodyStream<'_, R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        loop {
+            if this.done || buf.remaining() == 0 {
+                return Poll::Ready(Ok(()));
+            }
+            let written = this
+                .decode_pending(buf.initialize_unfilled())
+                .map_err(invalid_data)?;
+            if written > 0 {
+                buf.advance(written);
+                return Poll::Ready(Ok(()));
+            }
+            if this.done {
+                return Poll::Ready(Ok(()));
+            }
+            let mut chunk = [0; 4096];
+            let mut chunk_buf = ReadBuf::new(&mut chunk);
+            if Pin::new(&mut *this.source)
+                .poll_read(cx, &mut chunk_buf)?
+                .is_pending()
+            {
+                let read_timeout = this.read_timeout;
+                let stall = this
+                    .stall
+                    .get_or_insert_with(|| Box::pin(sleep(read_timeout)));
+                ready!(stall.as_mut().poll(cx));
+                let e = RequestParseError::BodyTimeout(read_timeout);
+                return Poll::Ready(Err(io::Error::new(io::ErrorKind::TimedOut, e)));
+            }
+            this.stall = None;
+            if chunk_buf.filled().is_empty() {
+                return Poll::Ready(Err(invalid_data(this.incomplete())));
+            }
+            this.pending.extend_from_slice(chunk_buf.filled());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    async fn read_all(input: &[u8], framing: Framing, max_size: usize) -> anyhow::Result<Vec<u8>> {
+        let mut source = input;
+        let mut pending = vec![];
+        let mut body = BodyStream::new(
+            &mut source,
+            &mut pending,
+            framing,
+            max_size,
+            Duration::from_secs(5),
+        )?;
+        let mut out = vec![];
+        body.read_to_end(&mut out).await.map_err(unwrap_io_error)?;
+        Ok(out)
+    }
+
+    #[tokio::test]
+    async fn test_body_stream_framing() {
+        let out = read_all(b"hello world", Framing::Length(5), 100).await;
+        assert_eq!(out.unwrap(), b"hello");
+        let chunked = b"5\r\nhello\r\n6;ext=1\r\n world\r\n0\r\nX-Trailer: 1\r\n\r\nGET";
+        let out = read_all(chunked, Framing::Chunked(ChunkedDecoder::new()), 100).await;
+        assert_eq!(out.unwrap(), b"hello world");
+        let out = read_all(b"abc--END--tail", Framing::Until(b"--END--".to_vec()), 100).await;
+        assert_eq!(out.unwrap(), b"abc--END--");
+    }
+
+    #[tokio::test]
+    async fn test_body_stream_limits() {
+        for framing in [Framing::Length(11), Framing::Chunked(ChunkedDecoder::new())] {
+            let input: &[u8] = match framing {
+                Framing::Length(_) => b"hello world",
+                _ => b"b\r\nhello world\r\n0\r\n\r\n",
+            };
+            let err = read_all(input, framing, 10).await.unwrap_err();
+            assert!(matches!(
+                err.downcast_ref(),
+                Some(RequestParseError::BodyTooLarge(10))
+            ));
+        }
+        let err = read_all(b"hel", Framing::Length(5), 10).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(RequestParseError::IncompleteBody)
+        ));
+    }
+}
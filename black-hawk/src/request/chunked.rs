@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+
+use super::{HttpHeaders, RequestBody};
+use crate::error::RequestParseError;
+
+// extensions and trailers are buffered, cap what a client can make us hold
+const MAX_METADATA: usize = 8 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChunkState {
+    Size,
+    Extension,
+    SizeLf,
+    Data(usize),
+    DataCr,
+    DataLf,
+    TrailerStart,
+    Trailer,
+    TrailerLf,
+    EndLf,
+    Done,
+}
+
+// Incremental decoder for `Transfer-Encoding: chunked`, fed with whatever
+// bytes have arrived so far. It never needs to look back, so input can be
+// split anywhere, and chunk data is copied as is, CRLF and binary included.
+#[derive(Debug, Clone)]
+pub struct ChunkedDecoder {
+    state: ChunkState,
+    size: usize,
+    digits: usize,
+    line: Vec<u8>,
+    metadata: usize,
+    sizes: Vec<usize>,
+    extensions: Vec<(usize, String)>,
+    trailers: Vec<(String, String)>,
+}
+
+impl Default for ChunkedDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChunkedDecoder {
+    pub fn new() -> Self {
+        Self {
+            state: ChunkState::Size,
+            size: 0,
+            digits: 0,
+            line: vec![],
+            metadata: 0,
+            sizes: vec![],
+            extensions: vec![],
+            trailers: vec![],
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.state == ChunkState::Done
+    }
+
+    // sizes of the data chunks seen so far, the last zero chunk excluded
+    pub fn sizes(&self) -> &[usize] {
+        &self.sizes
+    }
+
+    // `;`-extensions as written, keyed by the index of the chunk they came
+    // with, the last chunk has index `sizes().len()`
+    pub fn extensions(&self) -> &[(usize, String)] {
+        &self.extensions
+    }
+
+    pub fn trailers(&self) -> &[(String, String)] {
+        &self.trailers
+    }
+
+    pub fn into_body(self, content: Vec<u8>) -> RequestBody {
+        RequestBody::Chunked {
+            content,
+            sizes: self.sizes,
+            trailers: HttpHeaders(self.trailers.into_iter().collect::<HashMap<_, _>>()),
+        }
+    }
+
+    // Decodes from `input` into `out`, returning `(consumed, written)`. Stops
+    // early only when `out` is full or the final chunk has been read.
+    pub fn decode(
+        &mut self,
+        input: &[u8],
+        out: &mut [u8],
+    ) -> Result<(usize, usize), RequestParseError> {
+        let (mut consumed, mut written) = (0, 0);
+        while consumed < input.len() && self.state != ChunkState::Done {
+            if let ChunkState::Data(remaining) = self.state {
+                let n = remaining
+                    .min(input.len() - consumed)
+                    .min(out.len() - written);
+                if n == 0 {
+                    break;
+                }
+                out[written..written + n].copy_from_slice(&input[consumed..consumed + n]);
+                consumed += n;
+                written += n;
+                self.state = if remaining == n {
+                    ChunkState::DataCr
+                } else {
+                    ChunkState::Data(remaining - n)
+                };
+                continue;
+            }
+            self.step(input[consumed])?;
+            consumed += 1;
+        }
+        Ok((consumed, written))
+    }
+
+    fn step(&mut self, c: u8) -> Result<(), RequestParseError> {
+        self.state = match (self.state, c) {
+            (ChunkState::Size, c) if c.is_ascii_hexdigit() => {
+                let digit = (c as char).to_digit(16).unwrap() as usize;
+                self.size = self
+                    .size
+                    .checked_mul(16)
+                    .and_then(|s| s.checked_add(digit))
+                    .ok_or(RequestParseError::ParseChunkContent)?;
+                self.digits += 1;
+                ChunkState::Size
+            }
+            (ChunkState::Size, b';' | b' ' | b'\t') if self.digits > 0 => {
+                self.push_line(c)?;
+                ChunkState::Extension
+            }
+            (ChunkState::Size, b'\r') if self.digits > 0 => ChunkState::SizeLf,
+            (ChunkState::Extension, b'\r') => ChunkState::SizeLf,
+            (ChunkState::Extension, c) => {
+                self.push_line(c)?;
+                ChunkState::Extension
+            }
+            (ChunkState::SizeLf, b'\n') => self.end_size_line(),
+            (ChunkState::DataCr, b'\r') => ChunkState::DataLf,
+            (ChunkState::DataLf, b'\n') => ChunkState::Size,
+            (ChunkState::TrailerStart, b'\r') => ChunkState::EndLf,
+            (ChunkState::Trailer, b'\r') => ChunkState::TrailerLf,
+            (ChunkState::TrailerStart | ChunkState::Trailer, c) => {
+                self.push_line(c)?;
+                ChunkState::Trailer
+            }
+            (ChunkState::TrailerLf, b'\n') => {
+                self.end_trailer_line()?;
+                ChunkState::TrailerStart
+            }
+            (ChunkState::EndLf, b'\n') => ChunkState::Done,
+            _ => return Err(RequestParseError::ParseChunkContent),
+        };
+        Ok(())
+    }
+
+    fn push_line(&mut self, c: u8) -> Result<(), RequestParseError> {
+        self.metadata += 1;
+        if self.metadata > MAX_METADATA {
+            return Err(RequestParseError::ParseChunkContent);
+        }
+        self.line.push(c);
+        Ok(())
+    }
+
+    fn end_size_line(&mut self) -> ChunkState {
+        let extension = String::from_utf8_lossy(&self.line);
+        let extension = extension.trim_start_matches([' ', '\t']);
+        if let Some(extension) = extension.strip_prefix(';') {
+            self.extensions
+                .push((self.sizes.len(), extension.trim().to_string()));
+        }
+        self.line.clear();
+        let size = std::mem::take(&mut self.size);
+        self.digits = 0;
+        if size == 0 {
+            ChunkState::TrailerStart
+        } else {
+            self.sizes.push(size);
+            ChunkState::Data(size)
+        }
+    }
+
+    fn end_trailer_line(&mut self) -> Result<(), RequestParseError> {
+        let line = String::from_utf8_lossy(&self.line).to_string();
+        self.line.clear();
+        let (name, value) = line
+            .split_once(':')
+            .ok_or(RequestParseError::ParseChunkContent)?;
+        self.trailers
+            .push((name.trim().to_string(), value.trim().to_string()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // small deterministic generator so failures reproduce
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self) -> u64 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            self.0 >> 33
+        }
+
+        fn below(&mut self, n: u64) -> usize {
+            (self.next() % n) as usize
+        }
+    }
+
+    // feeds `pieces` one after another into a fresh decoder, with an output
+    // buffer of `out_len` bytes, the way `BodyStream` does
+    fn decode_pieces(
+        pieces: &[&[u8]],
+        out_len: usize,
+    ) -> Result<(Vec<u8>, ChunkedDecoder), RequestParseError> {
+        let mut decoder = ChunkedDecoder::new();
+        let mut content = vec![];
+        let mut pending = vec![];
+        let mut out = vec![0; out_len];
+        for piece in pieces {
+            pending.extend_from_slice(piece);
+            loop {
+                let (consumed, written) = decoder.decode(&pending, &mut out)?;
+                pending.drain(..consumed);
+                content.extend_from_slice(&out[..written]);
+                if written == 0 || decoder.is_done() {
+                    break;
+                }
+            }
+        }
+        if !decoder.is_done() {
+            return Err(RequestParseError::IncompleteChunkedBody);
+        }
+        Ok((content, decoder))
+    }
+
+    #[test]
+    fn test_extensions_and_trailers() {
+        let raw = b"5;name=value\r\nhe\r\nl\r\n3 ; a ; b=\"c\"\r\n\x00\xff\n\r\n0;last\r\nExpires: never\r\nX-Sum:  42 \r\n\r\n";
+        let (content, decoder) = decode_pieces(&[raw], 64).unwrap();
+        assert_eq!(content, b"he\r\nl\x00\xff\n");
+        assert_eq!(decoder.sizes(), [5, 3]);
+        assert_eq!(
+            decoder.extensions(),
+            [
+                (0, "name=value".to_string()),
+                (1, "a ; b=\"c\"".to_string()),
+                (2, "last".to_string())
+            ]
+        );
+        assert_eq!(
+            decoder.trailers(),
+            [
+                ("Expires".to_string(), "never".to_string()),
+                ("X-Sum".to_string(), "42".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_malformed_chunks() {
+        for raw in [
+            &b"\r\nhello\r\n0\r\n\r\n"[..],
+            b"5\r\nhelloX\r\n0\r\n\r\n",
+            b"5\nhello\r\n0\r\n\r\n",
+            b"g\r\n",
+            b"0\r\nno colon\r\n\r\n",
+            b"ffffffffffffffffff\r\n",
+        ] {
+            assert!(decode_pieces(&[raw], 64).is_err(), "{raw:?}");
+        }
+        assert!(matches!(
+            decode_pieces(&[b"5\r\nhello\r\n0\r\n"], 64),
+            Err(RequestParseError::IncompleteChunkedBody)
+        ));
+    }
+
+    fn random_encoding(rng: &mut Lcg) -> (Vec<u8>, Vec<u8>, Vec<usize>) {
+        let mut raw = vec![];
+        let mut content = vec![];
+        let mut sizes = vec![];
+        for _ in 0..rng.below(5) {
+            let size = 1 + rng.below(20);
+            // binary data biased towards CR, LF and digits
+            let data: Vec<u8> = (0..size)
+                .map(|_| match rng.below(4) {
+                    0 => b'\r',
+                    1 => b'\n',
+                    2 => b'0',
+                    _ => rng.below(256) as u8,
+                })
+                .collect();
+            raw.extend(format!("{size:x}").as_bytes());
+            if rng.below(3) == 0 {
+                raw.extend(b";ext=1");
+            }
+            raw.extend(b"\r\n");
+            raw.extend(&data);
+            raw.extend(b"\r\n");
+            content.extend(data);
+            sizes.push(size);
+        }
+        raw.extend(b"0\r\n");
+        if rng.below(2) == 0 {
+            raw.extend(b"X-Trailer: yes\r\n");
+        }
+        raw.extend(b"\r\n");
+        (raw, content, sizes)
+    }
+
+    #[test]
+    fn test_fuzz_every_split() {
+        let mut rng = Lcg(0x5eed);
+        for _ in 0..200 {
+            let (raw, content, sizes) = random_encoding(&mut rng);
+            // every single split point, plus a tiny output buffer
+            for at in 0..=raw.len() {
+                let (a, b) = raw.split_at(at);
+                let (decoded, decoder) = decode_pieces(&[a, b], 1 + at % 7).unwrap();
+                assert_eq!(decoded, content);
+                assert_eq!(decoder.sizes(), sizes);
+            }
+            // one byte at a time
+            let bytes: Vec<&[u8]> = raw.chunks(1).collect();
+            let (decoded, _) = decode_pieces(&bytes, 3).unwrap();
+            assert_eq!(decoded, content);
+            // anything cut short is incomplete, never a wrong body
+            for at in 0..raw.len() {
+                assert!(decode_pieces(&[&raw[..at]], 64).is_err());
+            }
+        }
+    }
+}
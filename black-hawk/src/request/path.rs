@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use crate::error::RequestParseError;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct HttpPath {
     abs_path: String,
     query: Vec<Query>,
@@ -99,4 +99,86 @@ impl HttpPath {
     pub fn anchor(&self) -> Option<&str> {
         self.anchor.as_deref()
     }
+
+    // the path percent-decoded with dot-segments resolved, see `normalize_path`
+    pub fn normalized(&self) -> Result<String, RequestParseError> {
+        normalize_path(&self.abs_path)
+    }
+}
+
+// Percent-decodes an absolute path and resolves `.` and `..` segments as in
+// RFC 3986 section 5.2.4, with empty segments dropped. Unlike the RFC, a `..`
+// that would climb above `/` is an error instead of being swallowed, and so
+// are NUL bytes and separators that only show up after decoding (`%2F`,
+// `%5C`), which would otherwise let one segment smuggle in several.
+pub fn normalize_path(raw: &str) -> Result<String, RequestParseError> {
+    let rest = raw
+        .strip_prefix('/')
+        .ok_or(RequestParseError::InvalidPath)?;
+    let mut segments: Vec<String> = vec![];
+    let mut trailing_slash = false;
+    for segment in rest.split('/') {
+        let decoded = urlencoding::decode_binary(segment.as_bytes());
+        if decoded.iter().any(|b| matches!(b, b'\0' | b'/' | b'\\')) {
+            return Err(RequestParseError::InvalidPath);
+        }
+        let decoded = String::from_utf8(decoded.into_owned())?;
+        // a path ending in `/`, `/.` or `/..` names a directory
+        trailing_slash = matches!(decoded.as_str(), "" | "." | "..");
+        match decoded.as_str() {
+            "" | "." => {}
+            ".." => {
+                segments.pop().ok_or(RequestParseError::InvalidPath)?;
+            }
+            _ => segments.push(decoded),
+        }
+    }
+    let mut path = format!("/{}", segments.join("/"));
+    if trailing_slash && !segments.is_empty() {
+        path.push('/');
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_normalize_path() {
+        for (raw, normalized) in [
+            ("/", "/"),
+            ("/index.html", "/index.html"),
+            ("/a/./b/../c", "/a/c"),
+            ("/a/b/..", "/a/"),
+            ("/a/b/.", "/a/b/"),
+            ("//a///b/", "/a/b/"),
+            ("/a/..", "/"),
+            ("/%61/%2e%2E/b%20c", "/b c"),
+            ("/caf%C3%A9.html", "/caf\u{e9}.html"),
+        ] {
+            assert_eq!(normalize_path(raw).unwrap(), normalized, "{raw}");
+        }
+    }
+
+    #[test]
+    fn test_normalize_path_rejects() {
+        for raw in [
+            "",
+            "index.html",
+            "/..",
+            "/../etc/passwd",
+            "/a/../../etc/passwd",
+            "/%2e%2e/etc/passwd",
+            "/.%2E/etc/passwd",
+            "/..%2fetc/passwd",
+            "/a%2F..%2F..%2Fetc",
+            "/..%5c..%5cwindows",
+            "/a\\b",
+            "/index.html%00.png",
+            "/%ff%fe",
+        ] {
+            assert!(normalize_path(raw).is_err(), "{raw}");
+        }
+    }
 }
@@ -0,0 +1,99 @@
+// one `Range: bytes=` spec, positions are inclusive like on the wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    // `first-last`
+    FromTo(u64, u64),
+    // `first-`
+    From(u64),
+    // `-suffix`, the last `suffix` bytes
+    Suffix(u64),
+}
+
+impl ByteRange {
+    // Inclusive `(first, last)` within a representation of `len` bytes, or
+    // `None` when the range does not overlap it.
+    pub fn resolve(&self, len: u64) -> Option<(u64, u64)> {
+        match *self {
+            ByteRange::FromTo(first, last) if first < len => Some((first, last.min(len - 1))),
+            ByteRange::From(first) if first < len => Some((first, len - 1)),
+            ByteRange::Suffix(suffix) if suffix > 0 && len > 0 => {
+                Some((len.saturating_sub(suffix), len - 1))
+            }
+            _ => None,
+        }
+    }
+}
+
+// Parses a `Range` header value. Anything other than a well formed `bytes`
+// range set gives `None`, and the caller should then ignore the header.
+pub fn parse_range_header(value: &str) -> Option<Vec<ByteRange>> {
+    let (unit, specs) = value.trim().split_once('=')?;
+    if !unit.trim().eq_ignore_ascii_case("bytes") {
+        return None;
+    }
+    let mut ranges = vec![];
+    for spec in specs.split(',') {
+        let spec = spec.trim();
+        if spec.is_empty() {
+            continue;
+        }
+        let (first, last) = spec.split_once('-')?;
+        let range = match (first.trim(), last.trim()) {
+            ("", suffix) => ByteRange::Suffix(suffix.parse().ok()?),
+            (first, "") => ByteRange::From(first.parse().ok()?),
+            (first, last) => {
+                let (first, last) = (first.parse().ok()?, last.parse().ok()?);
+                if first > last {
+                    return None;
+                }
+                ByteRange::FromTo(first, last)
+            }
+        };
+        ranges.push(range);
+    }
+    if ranges.is_empty() {
+        None
+    } else {
+        Some(ranges)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_range_header() {
+        assert_eq!(
+            parse_range_header("bytes=0-499, 500-999,-500 , 9500-"),
+            Some(vec![
+                ByteRange::FromTo(0, 499),
+                ByteRange::FromTo(500, 999),
+                ByteRange::Suffix(500),
+                ByteRange::From(9500),
+            ])
+        );
+        for invalid in [
+            "bytes=",
+            "bytes=5-1",
+            "bytes=a-b",
+            "bytes=-",
+            "items=0-1",
+            "0-1",
+            "bytes=1-2-3",
+        ] {
+            assert_eq!(parse_range_header(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_resolve_range() {
+        assert_eq!(ByteRange::FromTo(0, 499).resolve(100), Some((0, 99)));
+        assert_eq!(ByteRange::FromTo(100, 200).resolve(100), None);
+        assert_eq!(ByteRange::From(10).resolve(100), Some((10, 99)));
+        assert_eq!(ByteRange::Suffix(10).resolve(100), Some((90, 99)));
+        assert_eq!(ByteRange::Suffix(500).resolve(100), Some((0, 99)));
+        assert_eq!(ByteRange::Suffix(0).resolve(100), None);
+        assert_eq!(ByteRange::From(0).resolve(0), None);
+    }
+}
@@ -1,13 +1,15 @@
 // refactor response module
 
 pub(crate) mod body;
+pub mod compress;
 pub mod error;
-pub(crate) mod status;
+pub mod status;
 
-use std::collections::HashMap;
+use std::{collections::HashMap, io};
 
 use body::Body;
 use status::HttpStatus;
+use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 use crate::{consts::ContentType, request::HttpVersion};
 
@@ -21,7 +23,12 @@ pub struct Response {
 impl Response {
     pub fn new(status: HttpStatus, content_type: &ContentType, body: Body) -> Self {
         let mut headers = HashMap::new();
-        headers.insert("Content-Length".to_string(), body.len().to_string());
+        if status.allows_body() {
+            match body.content_length() {
+                Some(len) => headers.insert("Content-Length".to_string(), len.to_string()),
+                None => headers.insert("Transfer-Encoding".to_string(), "chunked".to_string()),
+            };
+        }
         headers.insert(
             "Content-Type".to_string(),
             content_type.as_str().to_string(),
@@ -38,6 +45,14 @@ impl Response {
             .insert(name.as_ref().to_string(), value.as_ref().to_string());
     }
 
+    pub fn remove_header(&mut self, name: impl AsRef<str>) -> Option<String> {
+        self.headers.remove(name.as_ref())
+    }
+
+    pub fn header(&self, name: impl AsRef<str>) -> Option<&str> {
+        self.headers.get(name.as_ref()).map(String::as_str)
+    }
+
     pub fn status(&self) -> &HttpStatus {
         &self.status
     }
@@ -46,6 +61,9 @@ impl Response {
         &self.body
     }
 
+    // head plus the body when it is already in memory, streaming bodies are
+    // only sent by `write_to`. Statuses that cannot carry a body never send
+    // one, whatever the handler put in.
     pub fn serialize(&self) -> Vec<u8> {
         let mut buffer = Vec::new();
         buffer.extend(HttpVersion::V1_1.to_string().as_bytes());
@@ -57,20 +75,137 @@ impl Response {
             buffer.extend(format!("{name}: {value}\r\n").as_bytes());
         }
         buffer.extend(b"\r\n");
-        if self
-            .headers
-            .get("Content-Length")
-            .unwrap_or(&"0".to_string())
-            .parse::<u64>()
-            .unwrap_or(0)
-            > 0
+        if self.status.allows_body()
+            && self
+                .headers
+                .get("Content-Length")
+                .unwrap_or(&"0".to_string())
+                .parse::<u64>()
+                .unwrap_or(0)
+                > 0
         {
-            match &self.body {
-                Body::RawText(s) => buffer.extend(s.as_bytes()),
-                Body::RawBinary(v) => buffer.extend(v),
-                Body::Json(j) => buffer.extend(j),
+            if let Some(bytes) = self.body.as_bytes() {
+                buffer.extend(bytes);
             }
         }
         buffer
     }
+
+    // fails before writing anything when a `Custom` status code does not
+    // fit the status line
+    pub async fn write_to<W: AsyncWrite + Unpin>(self, writer: &mut W) -> io::Result<()> {
+        self.status
+            .validate()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        writer.write_all(&self.serialize()).await?;
+        if !self.status.allows_body() {
+            return writer.flush().await;
+        }
+        match self.body {
+            Body::Stream(mut reader) => {
+                let mut buf = vec![0; 16 * 1024];
+                loop {
+                    let n = reader.read(&mut buf).await?;
+                    if n == 0 {
+                        break;
+                    }
+                    writer.write_all(format!("{n:x}\r\n").as_bytes()).await?;
+                    writer.write_all(&buf[..n]).await?;
+                    writer.write_all(b"\r\n").await?;
+                }
+                writer.write_all(b"0\r\n\r\n").await?;
+            }
+            Body::File { file, len } => {
+                let copied = tokio::io::copy(&mut file.take(len), writer).await?;
+                if copied != len {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+            }
+            _ => {}
+        }
+        writer.flush().await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_write_stream_body_chunked() {
+        let response = Response::new(
+            HttpStatus::Ok,
+            &ContentType::PlainText,
+            Body::stream(&b"hello world"[..]),
+        );
+        let mut out = vec![];
+        response.write_to(&mut out).await.unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("Transfer-Encoding: chunked\r\n"));
+        assert!(!out.contains("Content-Length"));
+        assert!(out.ends_with("\r\n\r\nb\r\nhello world\r\n0\r\n\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_bodyless_statuses() {
+        for status in [
+            HttpStatus::SwitchingProtocols,
+            HttpStatus::NoContent,
+            HttpStatus::NotModified,
+        ] {
+            for body in [
+                Body::RawText("ignored".to_string()),
+                Body::stream(&b"ignored"[..]),
+            ] {
+                let response = Response::new(status.clone(), &ContentType::PlainText, body);
+                let mut out = vec![];
+                response.write_to(&mut out).await.unwrap();
+                let out = String::from_utf8(out).unwrap();
+                assert!(out.starts_with(&format!("HTTP/1.1 {status}\r\n")));
+                assert!(out.ends_with("\r\n\r\n"), "{out}");
+                assert!(!out.contains("ignored"));
+                assert!(!out.contains("Content-Length"));
+                assert!(!out.contains("Transfer-Encoding"));
+            }
+        }
+        let response = Response::new(
+            HttpStatus::custom(299, "Fine").unwrap(),
+            &ContentType::PlainText,
+            Body::RawText("body".to_string()),
+        );
+        let out = String::from_utf8(response.serialize()).unwrap();
+        assert!(out.starts_with("HTTP/1.1 299 Fine\r\n"));
+        assert!(out.ends_with("\r\n\r\nbody"));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_custom_status() {
+        for code in [0, 99, 1000, u16::MAX] {
+            let response = Response::new(
+                HttpStatus::Custom(code, "Nope".into()),
+                &ContentType::PlainText,
+                Body::RawText("body".to_string()),
+            );
+            let mut out = vec![];
+            let err = response.write_to(&mut out).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+            assert!(out.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_file_body() {
+        let path = std::env::temp_dir().join(format!("black-hawk-body-{}", std::process::id()));
+        tokio::fs::write(&path, b"file content").await.unwrap();
+        let file = tokio::fs::File::open(&path).await.unwrap();
+        let body = Body::from_file(file).await.unwrap();
+        assert_eq!(body.content_length(), Some(12));
+        let response = Response::new(HttpStatus::Ok, &ContentType::PlainText, body);
+        let mut out = vec![];
+        response.write_to(&mut out).await.unwrap();
+        tokio::fs::remove_file(&path).await.unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("Content-Length: 12\r\n"));
+        assert!(out.ends_with("\r\n\r\nfile content"));
+    }
 }
@@ -1,20 +1,60 @@
+use std::{fmt::Debug, pin::Pin};
+
 use serde::Serialize;
+use tokio::{fs::File, io::AsyncRead};
 
 use super::error::ResponseError;
 
-#[derive(Debug)]
 pub enum Body {
     RawText(String),
     RawBinary(Vec<u8>),
     Json(Vec<u8>),
+    // length unknown up front, sent with `Transfer-Encoding: chunked`
+    Stream(Pin<Box<dyn AsyncRead + Send>>),
+    // copied from disk while writing the response, never held in memory whole
+    File { file: File, len: u64 },
 }
 
 impl Body {
-    pub fn len(&self) -> usize {
+    pub fn stream(reader: impl AsyncRead + Send + 'static) -> Self {
+        Body::Stream(Box::pin(reader))
+    }
+
+    pub async fn from_file(file: File) -> std::io::Result<Self> {
+        let len = file.metadata().await?.len();
+        Ok(Body::File { file, len })
+    }
+
+    // `None` when the body has to be sent chunked
+    pub fn content_length(&self) -> Option<u64> {
+        match self {
+            Body::RawText(s) => Some(s.len() as u64),
+            Body::RawBinary(b) => Some(b.len() as u64),
+            Body::Json(j) => Some(j.len() as u64),
+            Body::Stream(_) => None,
+            Body::File { len, .. } => Some(*len),
+        }
+    }
+
+    // the body bytes when they are already in memory
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Body::RawText(s) => Some(s.as_bytes()),
+            Body::RawBinary(b) => Some(b),
+            Body::Json(j) => Some(j),
+            Body::Stream(_) | Body::File { .. } => None,
+        }
+    }
+}
+
+impl Debug for Body {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Body::RawText(s) => s.len(),
-            Body::RawBinary(b) => b.len(),
-            Body::Json(j) => j.len(),
+            Body::RawText(s) => f.debug_tuple("RawText").field(s).finish(),
+            Body::RawBinary(b) => f.debug_tuple("RawBinary").field(b).finish(),
+            Body::Json(j) => f.debug_tuple("Json").field(j).finish(),
+            Body::Stream(_) => f.write_str("Stream(..)"),
+            Body::File { len, .. } => f.debug_struct("File").field("len", len).finish(),
         }
     }
 }
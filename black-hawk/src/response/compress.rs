@@ -0,0 +1,288 @@
+use std::{io, pin::Pin};
+
+use async_compression::tokio::bufread::{BrotliEncoder, GzipEncoder, ZlibEncoder};
+use tokio::io::{AsyncRead, AsyncReadExt, BufReader};
+
+use super::{body::Body, status::HttpStatus, Response};
+use crate::{consts::ContentType, CompressionConfig};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Brotli,
+    Gzip,
+    Deflate,
+}
+
+// our preference when the client weighs several codings the same
+const SUPPORTED: [Encoding; 3] = [Encoding::Brotli, Encoding::Gzip, Encoding::Deflate];
+
+impl Encoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+
+    fn matches(&self, coding: &str) -> bool {
+        coding.eq_ignore_ascii_case(self.as_str())
+            || (*self == Encoding::Gzip && coding.eq_ignore_ascii_case("x-gzip"))
+    }
+
+    fn encoder(self, reader: impl AsyncRead + Send + 'static) -> Pin<Box<dyn AsyncRead + Send>> {
+        let reader = BufReader::new(reader);
+        match self {
+            Encoding::Brotli => Box::pin(BrotliEncoder::new(reader)),
+            Encoding::Gzip => Box::pin(GzipEncoder::new(reader)),
+            // HTTP's `deflate` is the zlib format (RFC 9110 section 8.4.1.2),
+            // not a raw DEFLATE stream
+            Encoding::Deflate => Box::pin(ZlibEncoder::new(reader)),
+        }
+    }
+}
+
+// Picks the supported coding with the highest q-value in an `Accept-Encoding`
+// header, `*` standing in for codings not listed. `None` when nothing we
+// support is acceptable, or the client explicitly prefers `identity`.
+pub fn negotiate(accept_encoding: &str) -> Option<Encoding> {
+    let mut weights = vec![];
+    for item in accept_encoding.split(',') {
+        let mut params = item.split(';');
+        let coding = params.next().unwrap_or_default().trim();
+        if coding.is_empty() {
+            continue;
+        }
+        let mut q = 1.0;
+        for param in params {
+            if let Some((name, value)) = param.split_once('=') {
+                if name.trim().eq_ignore_ascii_case("q") {
+                    // a malformed weight counts as not acceptable
+                    q = value
+                        .trim()
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|q| (0.0..=1.0).contains(q))
+                        .unwrap_or(0.0);
+                }
+            }
+        }
+        weights.push((coding, q));
+    }
+    let weight = |matches: &dyn Fn(&str) -> bool| {
+        weights
+            .iter()
+            .find(|(coding, _)| matches(coding))
+            .or_else(|| weights.iter().find(|(coding, _)| *coding == "*"))
+            .map(|(_, q)| *q)
+    };
+    let mut best: Option<(Encoding, f32)> = None;
+    for encoding in SUPPORTED {
+        let q = weight(&|coding| encoding.matches(coding)).unwrap_or(0.0);
+        if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+            best = Some((encoding, q));
+        }
+    }
+    let (encoding, q) = best?;
+    match weights
+        .iter()
+        .find(|(coding, _)| coding.eq_ignore_ascii_case("identity"))
+    {
+        Some((_, identity)) if *identity > q => None,
+        _ => Some(encoding),
+    }
+}
+
+impl ContentType {
+    // formats that are not compressed already
+    pub fn is_compressible(&self) -> bool {
+        matches!(
+            self,
+            ContentType::PlainText
+                | ContentType::TextHtml
+                | ContentType::TextCss
+                | ContentType::TextJavascript
+                | ContentType::ImageSvg
+                | ContentType::ApplicationJson
+        )
+    }
+}
+
+impl Response {
+    fn is_compressible(&self, config: &CompressionConfig) -> bool {
+        // partial and bodyless responses describe the identity representation
+        if self.status.code() == HttpStatus::PartialContent.code()
+            || !self.status.allows_body()
+            || self.headers.contains_key("Content-Encoding")
+        {
+            return false;
+        }
+        let content_type = self
+            .headers
+            .get("Content-Type")
+            .map(|t| t.parse::<ContentType>());
+        if !matches!(content_type, Some(Ok(t)) if ContentType::is_compressible(&t)) {
+            return false;
+        }
+        match &self.body {
+            Body::Stream(_) => config.streaming,
+            Body::File { len, .. } => config.streaming && *len >= config.min_size,
+            body => body.content_length().unwrap_or(0) >= config.min_size,
+        }
+    }
+
+    // Compresses the body with the best coding the client accepts. In-memory
+    // bodies are compressed up front and keep their `Content-Length`, file
+    // and stream bodies are compressed as they are written and go chunked.
+    pub(crate) async fn compressed(
+        mut self,
+        accept_encoding: Option<&str>,
+        config: &CompressionConfig,
+    ) -> io::Result<Self> {
+        if !self.is_compressible(config) {
+            return Ok(self);
+        }
+        // caches must key on the header even for the identity response
+        let vary = match self.headers.get("Vary") {
+            Some(vary) if vary.contains("Accept-Encoding") => vary.clone(),
+            Some(vary) => format!("{vary}, Accept-Encoding"),
+            None => "Accept-Encoding".to_string(),
+        };
+        self.add_header("Vary", vary);
+        let Some(encoding) = accept_encoding.and_then(negotiate) else {
+            return Ok(self);
+        };
+        self.body = match self.body {
+            Body::Stream(reader) => Body::Stream(encoding.encoder(reader)),
+            Body::File { file, len } => Body::Stream(encoding.encoder(file.take(len))),
+            Body::RawText(s) => compress_bytes(encoding, s.into_bytes()).await?,
+            Body::RawBinary(b) | Body::Json(b) => compress_bytes(encoding, b).await?,
+        };
+        match self.body.content_length() {
+            Some(len) => self.add_header("Content-Length", len.to_string()),
+            None => {
+                self.remove_header("Content-Length");
+                self.add_header("Transfer-Encoding", "chunked");
+            }
+        }
+        self.add_header("Content-Encoding", encoding.as_str());
+        // the bytes differ from the identity file now, only weakly equal
+        if let Some(etag) = self.headers.get("ETag").filter(|e| !e.starts_with("W/")) {
+            let etag = format!("W/{etag}");
+            self.add_header("ETag", etag);
+        }
+        Ok(self)
+    }
+}
+
+async fn compress_bytes(encoding: Encoding, bytes: Vec<u8>) -> io::Result<Body> {
+    let mut compressed = vec![];
+    encoding
+        .encoder(io::Cursor::new(bytes))
+        .read_to_end(&mut compressed)
+        .await?;
+    Ok(Body::RawBinary(compressed))
+}
+
+#[cfg(test)]
+mod test {
+    use async_compression::tokio::bufread::{GzipDecoder, ZlibDecoder};
+
+    use super::*;
+
+    #[test]
+    fn test_negotiate() {
+        for (accept, expected) in [
+            ("gzip,deflate", Some(Encoding::Gzip)),
+            ("gzip, deflate, br", Some(Encoding::Brotli)),
+            ("deflate;q=0.5, gzip;q=0.4", Some(Encoding::Deflate)),
+            ("br;q=0, gzip;q=0.1", Some(Encoding::Gzip)),
+            ("x-gzip", Some(Encoding::Gzip)),
+            ("GZIP;Q=1.0", Some(Encoding::Gzip)),
+            ("*", Some(Encoding::Brotli)),
+            ("*;q=0.5, br;q=0", Some(Encoding::Gzip)),
+            ("identity;q=1, gzip;q=0.5", None),
+            ("identity;q=0.5, gzip", Some(Encoding::Gzip)),
+            ("gzip;q=0, deflate;q=0, br;q=0", None),
+            ("gzip;q=2", None),
+            ("gzip;q=abc", None),
+            ("compress, zstd", None),
+            ("identity", None),
+            ("", None),
+        ] {
+            assert_eq!(negotiate(accept), expected, "{accept}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compress_buffered_body() {
+        let config = CompressionConfig {
+            min_size: 16,
+            streaming: false,
+        };
+        let text = "hello hello hello hello hello hello";
+        let response = Response::new(
+            HttpStatus::Ok,
+            &ContentType::PlainText,
+            Body::RawText(text.to_string()),
+        );
+        let response = response.compressed(Some("gzip"), &config).await.unwrap();
+        assert_eq!(response.header("Content-Encoding"), Some("gzip"));
+        assert_eq!(response.header("Vary"), Some("Accept-Encoding"));
+        let compressed = response.body().as_bytes().unwrap();
+        assert_eq!(
+            response.header("Content-Length"),
+            Some(compressed.len().to_string().as_str())
+        );
+        let mut decoded = String::new();
+        GzipDecoder::new(compressed)
+            .read_to_string(&mut decoded)
+            .await
+            .unwrap();
+        assert_eq!(decoded, text);
+
+        let response = Response::new(
+            HttpStatus::Ok,
+            &ContentType::PlainText,
+            Body::RawText(text.to_string()),
+        );
+        let response = response.compressed(Some("deflate"), &config).await.unwrap();
+        assert_eq!(response.header("Content-Encoding"), Some("deflate"));
+        let mut decoded = String::new();
+        ZlibDecoder::new(response.body().as_bytes().unwrap())
+            .read_to_string(&mut decoded)
+            .await
+            .unwrap();
+        assert_eq!(decoded, text);
+
+        // too small, binary, streaming when that is turned off, or partial
+        let mut partial = Response::new(
+            HttpStatus::custom(206, "Partial").unwrap(),
+            &ContentType::PlainText,
+            Body::RawText(text.to_string()),
+        );
+        partial.add_header("Content-Range", "bytes 0-34/100");
+        for response in [
+            partial,
+            Response::new(
+                HttpStatus::Ok,
+                &ContentType::PlainText,
+                Body::RawText("hi".into()),
+            ),
+            Response::new(
+                HttpStatus::Ok,
+                &ContentType::ImagePng,
+                Body::RawBinary(vec![0; 64]),
+            ),
+            Response::new(
+                HttpStatus::Ok,
+                &ContentType::PlainText,
+                Body::stream(&b"stream"[..]),
+            ),
+        ] {
+            let response = response.compressed(Some("gzip"), &config).await.unwrap();
+            assert_eq!(response.header("Content-Encoding"), None);
+            assert_eq!(response.header("Vary"), None);
+        }
+    }
+}
@@ -1,40 +1,264 @@
-use std::fmt::Display;
+use std::{borrow::Cow, fmt::Display};
 
 use super::error::ResponseError;
 
-#[derive(Debug)]
-#[repr(u16)]
-pub enum HttpStatus {
-    Ok = 200,
-    Created = 201,
-    BadRequest = 400,
-    NotFound = 404,
-    InternalServerError = 500,
+// One variant per code in the IANA HTTP status code registry, with the
+// reason phrases of RFC 9110 and the RFCs that registered the rest.
+macro_rules! http_statuses {
+    ($($name:ident = $code:literal, $reason:literal;)*) => {
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub enum HttpStatus {
+            $($name,)*
+            // any other code, e.g. an unregistered or vendor specific one,
+            // with its own reason phrase. Build it with `HttpStatus::custom`,
+            // `Response::write_to` refuses codes outside 100..=999.
+            Custom(u16, Cow<'static, str>),
+        }
+
+        impl HttpStatus {
+            pub fn code(&self) -> u16 {
+                match self {
+                    $(HttpStatus::$name => $code,)*
+                    HttpStatus::Custom(code, _) => *code,
+                }
+            }
+
+            pub fn reason(&self) -> &str {
+                match self {
+                    $(HttpStatus::$name => $reason,)*
+                    HttpStatus::Custom(_, reason) => reason,
+                }
+            }
+
+            fn registered(code: u16) -> Option<Self> {
+                match code {
+                    $($code => Some(HttpStatus::$name),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+http_statuses! {
+    Continue = 100, "Continue";
+    SwitchingProtocols = 101, "Switching Protocols";
+    Processing = 102, "Processing";
+    EarlyHints = 103, "Early Hints";
+
+    Ok = 200, "OK";
+    Created = 201, "Created";
+    Accepted = 202, "Accepted";
+    NonAuthoritativeInformation = 203, "Non-Authoritative Information";
+    NoContent = 204, "No Content";
+    ResetContent = 205, "Reset Content";
+    PartialContent = 206, "Partial Content";
+    MultiStatus = 207, "Multi-Status";
+    AlreadyReported = 208, "Already Reported";
+    ImUsed = 226, "IM Used";
+
+    MultipleChoices = 300, "Multiple Choices";
+    MovedPermanently = 301, "Moved Permanently";
+    Found = 302, "Found";
+    SeeOther = 303, "See Other";
+    NotModified = 304, "Not Modified";
+    UseProxy = 305, "Use Proxy";
+    TemporaryRedirect = 307, "Temporary Redirect";
+    PermanentRedirect = 308, "Permanent Redirect";
+
+    BadRequest = 400, "Bad Request";
+    Unauthorized = 401, "Unauthorized";
+    PaymentRequired = 402, "Payment Required";
+    Forbidden = 403, "Forbidden";
+    NotFound = 404, "Not Found";
+    MethodNotAllowed = 405, "Method Not Allowed";
+    NotAcceptable = 406, "Not Acceptable";
+    ProxyAuthenticationRequired = 407, "Proxy Authentication Required";
+    RequestTimeout = 408, "Request Timeout";
+    Conflict = 409, "Conflict";
+    Gone = 410, "Gone";
+    LengthRequired = 411, "Length Required";
+    PreconditionFailed = 412, "Precondition Failed";
+    ContentTooLarge = 413, "Content Too Large";
+    UriTooLong = 414, "URI Too Long";
+    UnsupportedMediaType = 415, "Unsupported Media Type";
+    RangeNotSatisfiable = 416, "Range Not Satisfiable";
+    ExpectationFailed = 417, "Expectation Failed";
+    MisdirectedRequest = 421, "Misdirected Request";
+    UnprocessableContent = 422, "Unprocessable Content";
+    Locked = 423, "Locked";
+    FailedDependency = 424, "Failed Dependency";
+    TooEarly = 425, "Too Early";
+    UpgradeRequired = 426, "Upgrade Required";
+    PreconditionRequired = 428, "Precondition Required";
+    TooManyRequests = 429, "Too Many Requests";
+    RequestHeaderFieldsTooLarge = 431, "Request Header Fields Too Large";
+    UnavailableForLegalReasons = 451, "Unavailable For Legal Reasons";
+
+    InternalServerError = 500, "Internal Server Error";
+    NotImplemented = 501, "Not Implemented";
+    BadGateway = 502, "Bad Gateway";
+    ServiceUnavailable = 503, "Service Unavailable";
+    GatewayTimeout = 504, "Gateway Timeout";
+    HttpVersionNotSupported = 505, "HTTP Version Not Supported";
+    VariantAlsoNegotiates = 506, "Variant Also Negotiates";
+    InsufficientStorage = 507, "Insufficient Storage";
+    LoopDetected = 508, "Loop Detected";
+    NotExtended = 510, "Not Extended";
+    NetworkAuthenticationRequired = 511, "Network Authentication Required";
+}
+
+impl HttpStatus {
+    // `Custom` even when the code is registered, to send another reason
+    pub fn custom(code: u16, reason: impl Into<Cow<'static, str>>) -> Result<Self, ResponseError> {
+        let status = HttpStatus::Custom(code, reason.into());
+        status.validate()?;
+        Ok(status)
+    }
+
+    // the status line has room for three digits only
+    pub(crate) fn validate(&self) -> Result<(), ResponseError> {
+        match self.code() {
+            100..=999 => Ok(()),
+            code => Err(ResponseError::InvalidStatusCode(code)),
+        }
+    }
+
+    pub fn is_informational(&self) -> bool {
+        (100..200).contains(&self.code())
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.code())
+    }
+
+    pub fn is_redirect(&self) -> bool {
+        (300..400).contains(&self.code())
+    }
+
+    pub fn is_client_error(&self) -> bool {
+        (400..500).contains(&self.code())
+    }
+
+    pub fn is_server_error(&self) -> bool {
+        (500..600).contains(&self.code())
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.is_client_error() || self.is_server_error()
+    }
+
+    // 1xx, 204 and 304 responses end right after the header section
+    pub fn allows_body(&self) -> bool {
+        !(self.is_informational() || matches!(self.code(), 204 | 304))
+    }
 }
 
 impl Display for HttpStatus {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            HttpStatus::Ok => write!(f, "200 OK"),
-            HttpStatus::Created => write!(f, "201 Created"),
-            HttpStatus::BadRequest => write!(f, "400 Bad Request"),
-            HttpStatus::NotFound => write!(f, "404 Not Found"),
-            HttpStatus::InternalServerError => write!(f, "500 Internal Server Error"),
+        write!(f, "{} ", self.code())?;
+        // a custom reason must not be able to end the status line early
+        for c in self
+            .reason()
+            .chars()
+            .filter(|c| !c.is_control() || *c == '\t')
+        {
+            write!(f, "{c}")?;
         }
+        Ok(())
     }
 }
 
+// registered codes map to their variant, any other three digit code becomes
+// `Custom` with an empty reason, which HTTP/1.1 allows
 impl TryFrom<u16> for HttpStatus {
     type Error = ResponseError;
 
     fn try_from(value: u16) -> Result<Self, Self::Error> {
-        match value {
-            200 => Ok(HttpStatus::Ok),
-            201 => Ok(HttpStatus::Created),
-            400 => Ok(HttpStatus::BadRequest),
-            404 => Ok(HttpStatus::NotFound),
-            500 => Ok(HttpStatus::InternalServerError),
-            _ => Err(ResponseError::InvalidStatusCode(value)),
+        match HttpStatus::registered(value) {
+            Some(status) => Ok(status),
+            None => HttpStatus::custom(value, ""),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_status_codes() {
+        for code in 100..=999 {
+            let status = HttpStatus::try_from(code).unwrap();
+            assert_eq!(status.code(), code);
+            if !matches!(status, HttpStatus::Custom(..)) {
+                assert!(!status.reason().is_empty(), "{code}");
+            }
+        }
+        assert_eq!(
+            HttpStatus::try_from(429).unwrap(),
+            HttpStatus::TooManyRequests
+        );
+        assert_eq!(
+            HttpStatus::try_from(418).unwrap(),
+            HttpStatus::custom(418, "").unwrap()
+        );
+        assert!(HttpStatus::try_from(99).is_err());
+        assert!(HttpStatus::try_from(1000).is_err());
+        for code in [0, 42, 99, 1000, u16::MAX] {
+            assert!(matches!(
+                HttpStatus::custom(code, "Nope"),
+                Err(ResponseError::InvalidStatusCode(c)) if c == code
+            ));
+        }
+    }
+
+    #[test]
+    fn test_status_display() {
+        assert_eq!(HttpStatus::Ok.to_string(), "200 OK");
+        assert_eq!(
+            HttpStatus::ContentTooLarge.to_string(),
+            "413 Content Too Large"
+        );
+        assert_eq!(
+            HttpStatus::custom(599, "Network Connect Timeout")
+                .unwrap()
+                .to_string(),
+            "599 Network Connect Timeout"
+        );
+        assert_eq!(
+            HttpStatus::custom(200, "OK\r\nX-Injected: 1")
+                .unwrap()
+                .to_string(),
+            "200 OKX-Injected: 1"
+        );
+        assert_eq!(HttpStatus::try_from(799).unwrap().to_string(), "799 ");
+    }
+
+    #[test]
+    fn test_status_classes() {
+        assert!(HttpStatus::EarlyHints.is_informational());
+        assert!(HttpStatus::NoContent.is_success());
+        assert!(HttpStatus::PermanentRedirect.is_redirect());
+        assert!(HttpStatus::NotModified.is_redirect());
+        assert!(HttpStatus::UnavailableForLegalReasons.is_client_error());
+        assert!(HttpStatus::ServiceUnavailable.is_server_error());
+        assert!(HttpStatus::custom(599, "").unwrap().is_error());
+        assert!(!HttpStatus::custom(799, "").unwrap().is_error());
+
+        for status in [
+            HttpStatus::Continue,
+            HttpStatus::NoContent,
+            HttpStatus::NotModified,
+        ] {
+            assert!(!status.allows_body(), "{status}");
+        }
+        for status in [
+            HttpStatus::Ok,
+            HttpStatus::ResetContent,
+            HttpStatus::NotFound,
+        ] {
+            assert!(status.allows_body(), "{status}");
         }
     }
 }
@@ -8,7 +8,8 @@ use crate::{
 };
 
 pub trait Router {
-    fn route(&self, request: &mut HttpRequest) -> Option<&dyn Handler>;
+    // only looks at the head, so works before or after the body is read
+    fn route<B>(&self, request: &mut HttpRequest<B>) -> Option<&dyn Handler>;
 }
 
 pub struct StaticRouter {
@@ -24,7 +25,7 @@ pub struct StaticRouter {
 }
 
 impl Router for StaticRouter {
-    fn route(&self, request: &mut HttpRequest) -> Option<&dyn Handler> {
+    fn route<B>(&self, request: &mut HttpRequest<B>) -> Option<&dyn Handler> {
         let path = request.header().path.abs_path();
         match request.header().method {
             crate::request::HttpMethod::Get => route_with_prefix(&self.get, path),
@@ -138,10 +139,10 @@ pub struct DynamicRouter {
     path_vars: HashMap<String, String>,
 }
 
-fn route_with_path_variable<'t>(
+fn route_with_path_variable<'t, B>(
     trie: &'t Trie<String, Box<dyn Handler>>,
     path_vars: &HashMap<String, String>,
-    request: &mut HttpRequest,
+    request: &mut HttpRequest<B>,
 ) -> Option<&'t dyn Handler> {
     let mut prefix = String::from("/");
     let path = request.header().path.abs_path().to_string();
@@ -216,7 +217,7 @@ impl DynamicRouter {
 }
 
 impl Router for DynamicRouter {
-    fn route(&self, request: &mut HttpRequest) -> Option<&dyn Handler> {
+    fn route<B>(&self, request: &mut HttpRequest<B>) -> Option<&dyn Handler> {
         match request.header.method {
             HttpMethod::Get => route_with_path_variable(&self.get, &self.path_vars, request),
             HttpMethod::Post => route_with_path_variable(&self.post, &self.path_vars, request),
@@ -1,13 +1,28 @@
-use cache_mem::{emulate_counters, Counters, Matrix};
-use criterion::{criterion_group, criterion_main, Criterion};
+use cache_mem::{
+    emulate_counters, emulate_sharded, Counters, Matrix, PackedCounters, ShardedCounter,
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+const ITERATIONS: u64 = 1_000_000;
+const THREADS: [usize; 4] = [1, 2, 4, 8];
 
 fn bench_counters(c: &mut Criterion) {
-    c.bench_function("bench_counters", |b| {
-        let counters = Counters::default();
-        b.iter(|| {
-            emulate_counters(&counters);
+    let mut group = c.benchmark_group("bench_counters");
+    for threads in THREADS {
+        group.bench_with_input(BenchmarkId::new("padded", threads), &threads, |b, &t| {
+            let counters: Counters<8> = Counters::new();
+            b.iter(|| emulate_counters(&counters, t, ITERATIONS));
         });
-    });
+        group.bench_with_input(BenchmarkId::new("packed", threads), &threads, |b, &t| {
+            let counters: PackedCounters<8> = PackedCounters::new();
+            b.iter(|| emulate_counters(&counters, t, ITERATIONS));
+        });
+        group.bench_with_input(BenchmarkId::new("sharded", threads), &threads, |b, &t| {
+            let counter = ShardedCounter::new(t);
+            b.iter(|| emulate_sharded(&counter, t, ITERATIONS));
+        });
+    }
+    group.finish();
 }
 
 fn bench_mul_matrix(c: &mut Criterion) {
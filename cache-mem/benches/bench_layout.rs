@@ -0,0 +1,59 @@
+use cache_mem::{ColumnMajor, Grid, Layout, Matrix, RowMajor, Tiled, ZOrder};
+use criterion::measurement::WallTime;
+use criterion::{criterion_group, criterion_main, BenchmarkGroup, BenchmarkId, Criterion};
+
+const SIZES: [usize; 3] = [256, 1024, 2048];
+
+// every workload runs unchanged on every layout, one criterion group per workload
+fn bench_on<L: Layout>(
+    group: &mut BenchmarkGroup<'_, WallTime>,
+    layout: L,
+    matrix: &Matrix,
+    workload: fn(&Grid<L>),
+) {
+    let grid = Grid::from_matrix(matrix, layout);
+    group.bench_with_input(
+        BenchmarkId::new(layout.name(), matrix.rows()),
+        &grid,
+        |b, grid| b.iter(|| workload(grid)),
+    );
+}
+
+macro_rules! bench_workload {
+    ($name: ident, $workload: expr) => {
+        fn $name(c: &mut Criterion) {
+            let mut group = c.benchmark_group(stringify!($name));
+            group.sample_size(10);
+            for n in SIZES {
+                let matrix = Matrix::from_random(n);
+                bench_on(&mut group, RowMajor, &matrix, $workload);
+                bench_on(&mut group, ColumnMajor, &matrix, $workload);
+                bench_on(&mut group, ZOrder, &matrix, $workload);
+                bench_on(&mut group, Tiled::new(16), &matrix, $workload);
+            }
+            group.finish();
+        }
+    };
+}
+
+bench_workload!(bench_row_scan, |g| {
+    std::hint::black_box(g.row_scan());
+});
+bench_workload!(bench_col_scan, |g| {
+    std::hint::black_box(g.col_scan());
+});
+bench_workload!(bench_stencil, |g| {
+    std::hint::black_box(g.stencil());
+});
+bench_workload!(bench_layout_transpose, |g| {
+    std::hint::black_box(g.transpose());
+});
+
+criterion_group!(
+    benches,
+    bench_row_scan,
+    bench_col_scan,
+    bench_stencil,
+    bench_layout_transpose
+);
+criterion_main!(benches);
@@ -0,0 +1,63 @@
+use cache_mem::Matrix;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+const SIZES: [usize; 4] = [256, 512, 1024, 2048];
+
+fn bench_mul_variants(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bench_mul_variants");
+    group.sample_size(10);
+    for n in SIZES {
+        let lhs = Matrix::from_random(n);
+        let rhs = Matrix::from_random(n);
+        group.bench_with_input(BenchmarkId::new("transposed", n), &n, |b, _| {
+            b.iter(|| lhs.mul_matrix(&rhs))
+        });
+        group.bench_with_input(BenchmarkId::new("ikj", n), &n, |b, _| {
+            b.iter(|| lhs.mul_matrix_ikj(&rhs))
+        });
+        for tile in [32, 64] {
+            group.bench_with_input(BenchmarkId::new(format!("tiled_{tile}"), n), &n, |b, _| {
+                b.iter(|| lhs.mul_matrix_tiled(&rhs, tile))
+            });
+        }
+        group.bench_with_input(BenchmarkId::new("simd", n), &n, |b, _| {
+            b.iter(|| lhs.mul_matrix_simd(&rhs))
+        });
+    }
+    group.finish();
+}
+
+fn bench_par_mul_matrix(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bench_par_mul_matrix");
+    group.sample_size(10);
+    let lhs = Matrix::from_random(1024);
+    let rhs = Matrix::from_random(1024);
+    for threads in [1, 2, 4, 8, 16] {
+        group.bench_with_input(BenchmarkId::from_parameter(threads), &threads, |b, &t| {
+            b.iter(|| lhs.par_mul_matrix(&rhs, t))
+        });
+    }
+    group.finish();
+}
+
+fn bench_transpose(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bench_transpose");
+    for n in SIZES {
+        let m = Matrix::from_random(n);
+        group.bench_with_input(BenchmarkId::new("naive", n), &n, |b, _| {
+            b.iter(|| m.transpose())
+        });
+        group.bench_with_input(BenchmarkId::new("recursive", n), &n, |b, _| {
+            b.iter(|| m.transpose_recursive())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_mul_variants,
+    bench_par_mul_matrix,
+    bench_transpose
+);
+criterion_main!(benches);
@@ -0,0 +1,51 @@
+use cache_mem::{
+    random_particles, step_fat, step_hot, AosParticles, FatParticle, ParticleStore, SoaParticles,
+    SplitParticles,
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+const SIZES: [usize; 3] = [1_000, 100_000, 1_000_000];
+
+fn bench_update<S: ParticleStore>(c: &mut Criterion, name: &str) {
+    let mut group = c.benchmark_group(format!("bench_particles_{name}"));
+    for n in SIZES {
+        let particles = random_particles(n);
+        let mut store = S::from_particles(&particles);
+        group.bench_with_input(BenchmarkId::new("update", n), &n, |b, _| {
+            b.iter(|| store.update(0.01, -9.81))
+        });
+        group.bench_with_input(BenchmarkId::new("kinetic_energy", n), &n, |b, _| {
+            b.iter(|| store.kinetic_energy())
+        });
+        group.bench_with_input(BenchmarkId::new("center_of_mass", n), &n, |b, _| {
+            b.iter(|| store.center_of_mass())
+        });
+    }
+    group.finish();
+}
+
+fn bench_aos(c: &mut Criterion) {
+    bench_update::<AosParticles>(c, "aos");
+}
+
+fn bench_soa(c: &mut Criterion) {
+    bench_update::<SoaParticles>(c, "soa");
+}
+
+fn bench_hot_cold(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bench_hot_cold");
+    for n in SIZES {
+        let mut fat = vec![FatParticle::default(); n];
+        let mut split = SplitParticles::from(fat.as_slice());
+        group.bench_with_input(BenchmarkId::new("fat", n), &n, |b, _| {
+            b.iter(|| step_fat(&mut fat, 0.01))
+        });
+        group.bench_with_input(BenchmarkId::new("hot", n), &n, |b, _| {
+            b.iter(|| step_hot(&mut split.hot, 0.01))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_aos, bench_soa, bench_hot_cold);
+criterion_main!(benches);
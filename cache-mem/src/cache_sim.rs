@@ -0,0 +1,343 @@
+use std::{
+    fmt::Display,
+    ops::Index,
+    sync::{atomic::AtomicU64, Mutex},
+};
+
+use crate::CounterSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheConfig {
+    pub line_size: usize,
+    pub associativity: usize,
+    pub capacity: usize,
+}
+
+impl CacheConfig {
+    pub fn new(line_size: usize, associativity: usize, capacity: usize) -> Self {
+        assert!(line_size > 0 && associativity > 0, "empty cache geometry");
+        assert!(
+            capacity > 0 && capacity.is_multiple_of(line_size * associativity),
+            "capacity must be a positive multiple of line_size * associativity"
+        );
+        Self {
+            line_size,
+            associativity,
+            capacity,
+        }
+    }
+
+    // 32 KiB, 8-way, 64 byte lines
+    pub fn l1() -> Self {
+        Self::new(64, 8, 32 * 1024)
+    }
+
+    // 1 MiB, 16-way, 64 byte lines
+    pub fn l2() -> Self {
+        Self::new(64, 16, 1024 * 1024)
+    }
+
+    pub fn sets(&self) -> usize {
+        self.capacity / (self.line_size * self.associativity)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LevelStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl LevelStats {
+    pub fn accesses(&self) -> u64 {
+        self.hits + self.misses
+    }
+
+    pub fn miss_rate(&self) -> f64 {
+        if self.accesses() == 0 {
+            0.0
+        } else {
+            self.misses as f64 / self.accesses() as f64
+        }
+    }
+}
+
+// one set-associative level, every set keeps its tags most recently used first
+#[derive(Debug, Clone)]
+pub struct CacheLevel {
+    config: CacheConfig,
+    sets: Vec<Vec<usize>>,
+    stats: LevelStats,
+}
+
+impl CacheLevel {
+    pub fn new(config: CacheConfig) -> Self {
+        Self {
+            config,
+            sets: vec![Vec::with_capacity(config.associativity); config.sets()],
+            stats: LevelStats::default(),
+        }
+    }
+
+    pub fn config(&self) -> CacheConfig {
+        self.config
+    }
+
+    pub fn stats(&self) -> LevelStats {
+        self.stats
+    }
+
+    // returns whether the line holding `addr` was cached, a miss fills it and
+    // evicts the least recently used line of the set
+    pub fn access(&mut self, addr: usize) -> bool {
+        let line = addr / self.config.line_size;
+        let set = &mut self.sets[line % self.config.sets()];
+        let tag = line / self.config.sets();
+        match set.iter().position(|&t| t == tag) {
+            Some(i) => {
+                set[..=i].rotate_right(1);
+                self.stats.hits += 1;
+                true
+            }
+            None => {
+                if set.len() == self.config.associativity {
+                    set.pop();
+                }
+                set.insert(0, tag);
+                self.stats.misses += 1;
+                false
+            }
+        }
+    }
+}
+
+// L1 backed by L2, an access only reaches L2 when it misses L1
+#[derive(Debug, Clone)]
+pub struct CacheSim {
+    l1: CacheLevel,
+    l2: CacheLevel,
+}
+
+impl CacheSim {
+    pub fn new(l1: CacheConfig, l2: CacheConfig) -> Self {
+        Self {
+            l1: CacheLevel::new(l1),
+            l2: CacheLevel::new(l2),
+        }
+    }
+
+    pub fn access(&mut self, addr: usize) {
+        if !self.l1.access(addr) {
+            self.l2.access(addr);
+        }
+    }
+
+    pub fn run(&mut self, trace: impl IntoIterator<Item = usize>) {
+        for addr in trace {
+            self.access(addr);
+        }
+    }
+
+    pub fn l1(&self) -> LevelStats {
+        self.l1.stats()
+    }
+
+    pub fn l2(&self) -> LevelStats {
+        self.l2.stats()
+    }
+}
+
+impl Default for CacheSim {
+    fn default() -> Self {
+        Self::new(CacheConfig::l1(), CacheConfig::l2())
+    }
+}
+
+impl Display for CacheSim {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, stats) in [("L1", self.l1()), ("L2", self.l2())] {
+            writeln!(
+                f,
+                "{name}: {} hits, {} misses ({:.2}% miss rate)",
+                stats.hits,
+                stats.misses,
+                100.0 * stats.miss_rate()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Addresses recorded by `Traced` wrappers, in access order. Threads share one
+// trace, so the simulator sees their accesses interleaved as on one core.
+#[derive(Debug, Default)]
+pub struct Trace(Mutex<Vec<usize>>);
+
+impl Trace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record<T: ?Sized>(&self, ptr: *const T) {
+        self.0.lock().unwrap().push(ptr.cast::<u8>() as usize);
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn into_addresses(self) -> Vec<usize> {
+        self.0.into_inner().unwrap()
+    }
+}
+
+// Instrumented indexing mode: every element reached through the wrapper is
+// recorded in the trace, then handed out exactly as the inner container would.
+#[derive(Debug, Clone, Copy)]
+pub struct Traced<'a, M: ?Sized> {
+    inner: &'a M,
+    trace: &'a Trace,
+}
+
+impl<'a, M: ?Sized> Traced<'a, M> {
+    pub fn new(inner: &'a M, trace: &'a Trace) -> Self {
+        Self { inner, trace }
+    }
+
+    pub fn inner(&self) -> &'a M {
+        self.inner
+    }
+}
+
+impl<M: Index<I> + ?Sized, I> Index<I> for Traced<'_, M> {
+    type Output = M::Output;
+
+    fn index(&self, index: I) -> &Self::Output {
+        let element = &self.inner[index];
+        self.trace.record(element);
+        element
+    }
+}
+
+impl<C: CounterSet> CounterSet for Traced<'_, C> {
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn increment(&self, i: usize) {
+        self.trace.record(self.inner.slot(i));
+        self.inner.increment(i)
+    }
+
+    fn get(&self, i: usize) -> u64 {
+        self.trace.record(self.inner.slot(i));
+        self.inner.get(i)
+    }
+
+    fn slot(&self, i: usize) -> &AtomicU64 {
+        self.inner.slot(i)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{emulate_counters, ColumnMajor, Counters, Grid, Matrix, PackedCounters, RowMajor};
+
+    #[test]
+    fn test_lru_eviction() {
+        // 2 sets, 2 ways, 16 byte lines
+        let mut level = CacheLevel::new(CacheConfig::new(16, 2, 64));
+        assert!(!level.access(0));
+        assert!(level.access(15));
+        assert!(!level.access(32));
+        assert!(level.access(0));
+        // set 0 now holds lines 0 (MRU) and 2, line 4 evicts line 2
+        assert!(!level.access(64));
+        assert!(level.access(0));
+        assert!(!level.access(32));
+        // line 1 maps to the other set
+        assert!(!level.access(16));
+        assert_eq!(level.stats(), LevelStats { hits: 3, misses: 5 });
+    }
+
+    #[test]
+    fn test_l2_sees_only_l1_misses() {
+        let mut sim = CacheSim::new(CacheConfig::new(64, 1, 128), CacheConfig::new(64, 4, 1024));
+        sim.run([0, 128, 0, 128, 136]);
+        assert_eq!(sim.l1(), LevelStats { hits: 1, misses: 4 });
+        assert_eq!(sim.l2(), LevelStats { hits: 2, misses: 2 });
+        assert!(sim.to_string().starts_with("L1: 1 hits, 4 misses"));
+    }
+
+    fn scan(m: &impl Index<(usize, usize), Output = f64>, n: usize, by_row: bool) -> f64 {
+        let mut sum = 0.0;
+        for a in 0..n {
+            for b in 0..n {
+                sum += if by_row { m[(a, b)] } else { m[(b, a)] };
+            }
+        }
+        sum
+    }
+
+    #[test]
+    fn test_matrix_scan_misses() {
+        let n = 256;
+        let matrix = Matrix::from_fn(n, n, |i, j| (i + j) as f64);
+        let mut misses = vec![];
+        for by_row in [true, false] {
+            let trace = Trace::new();
+            scan(&Traced::new(&matrix, &trace), n, by_row);
+            assert_eq!(trace.len(), n * n);
+            let mut sim = CacheSim::default();
+            sim.run(trace.into_addresses());
+            misses.push(sim.l1().misses);
+        }
+        // row scan misses once per line (plus one if the buffer is not line
+        // aligned), column scan once per element
+        let lines = (n * n / 8) as u64;
+        assert!(misses[0] == lines || misses[0] == lines + 1);
+        assert_eq!(misses[1], (n * n) as u64);
+
+        // the same column scan is line friendly on a column-major grid
+        let grid = Grid::from_matrix(&matrix, ColumnMajor);
+        let trace = Trace::new();
+        scan(&Traced::new(&grid, &trace), n, false);
+        let mut sim = CacheSim::default();
+        sim.run(trace.into_addresses());
+        assert!(sim.l1().misses <= lines + 1);
+        let grid = Grid::from_matrix(&matrix, RowMajor);
+        assert_eq!(scan(&grid, n, true), scan(&matrix, n, true));
+    }
+
+    #[test]
+    fn test_counters_trace() {
+        let mut lines = vec![];
+        {
+            let counters: Counters<8> = Counters::new();
+            let trace = Trace::new();
+            emulate_counters(&Traced::new(&counters, &trace), 8, 10);
+            assert_eq!(counters.get(7), 10);
+            lines.push(trace_lines(trace));
+        }
+        {
+            let counters: PackedCounters<8> = PackedCounters::new();
+            let trace = Trace::new();
+            emulate_counters(&Traced::new(&counters, &trace), 8, 10);
+            lines.push(trace_lines(trace));
+        }
+        // padded counters touch one line each, packed ones share a line or two
+        assert_eq!(lines[0], 8);
+        assert!(lines[1] <= 2);
+    }
+
+    fn trace_lines(trace: Trace) -> u64 {
+        let mut sim = CacheSim::default();
+        sim.run(trace.into_addresses());
+        sim.l1().misses
+    }
+}
@@ -0,0 +1,183 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    thread,
+};
+
+pub trait CounterSet: Sync {
+    fn len(&self) -> usize;
+    fn increment(&self, i: usize);
+    fn get(&self, i: usize) -> u64;
+    // the atomic behind counter `i`, lets instrumented wrappers see addresses
+    fn slot(&self, i: usize) -> &AtomicU64;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// one counter per cache line, threads never touch each other's line
+#[derive(Debug)]
+pub struct Counters<const N: usize = 4>([Counter; N]);
+
+#[derive(Debug)]
+#[repr(align(64))]
+pub struct Counter(AtomicU64);
+
+impl From<AtomicU64> for Counter {
+    fn from(value: AtomicU64) -> Self {
+        Self(value)
+    }
+}
+
+impl<const N: usize> Counters<N> {
+    pub fn new() -> Self {
+        Self(std::array::from_fn(|_| AtomicU64::new(0).into()))
+    }
+}
+
+impl<const N: usize> Default for Counters<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> CounterSet for Counters<N> {
+    fn len(&self) -> usize {
+        N
+    }
+
+    fn increment(&self, i: usize) {
+        self.0[i].0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn get(&self, i: usize) -> u64 {
+        self.0[i].0.load(Ordering::Relaxed)
+    }
+
+    fn slot(&self, i: usize) -> &AtomicU64 {
+        &self.0[i].0
+    }
+}
+
+// same counters packed next to each other, 8 of them share one cache line
+// and every increment bounces that line between cores (false sharing)
+#[derive(Debug)]
+pub struct PackedCounters<const N: usize = 4>([AtomicU64; N]);
+
+impl<const N: usize> PackedCounters<N> {
+    pub fn new() -> Self {
+        Self(std::array::from_fn(|_| AtomicU64::new(0)))
+    }
+}
+
+impl<const N: usize> Default for PackedCounters<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> CounterSet for PackedCounters<N> {
+    fn len(&self) -> usize {
+        N
+    }
+
+    fn increment(&self, i: usize) {
+        self.0[i].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn get(&self, i: usize) -> u64 {
+        self.0[i].load(Ordering::Relaxed)
+    }
+
+    fn slot(&self, i: usize) -> &AtomicU64 {
+        &self.0[i]
+    }
+}
+
+// a single logical counter split into padded per-thread cells,
+// writes stay core-local and a read sums all cells
+#[derive(Debug)]
+pub struct ShardedCounter(Vec<Counter>);
+
+impl ShardedCounter {
+    pub fn new(shards: usize) -> Self {
+        assert!(shards > 0, "a sharded counter needs at least one shard");
+        Self((0..shards).map(|_| AtomicU64::new(0).into()).collect())
+    }
+
+    pub fn shards(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn add(&self, shard: usize, n: u64) {
+        self.0[shard % self.0.len()]
+            .0
+            .fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn sum(&self) -> u64 {
+        self.0.iter().map(|c| c.0.load(Ordering::Relaxed)).sum()
+    }
+}
+
+// thread `i` increments counter `i % counters.len()` `iterations` times
+pub fn emulate_counters(counters: &impl CounterSet, threads: usize, iterations: u64) {
+    assert!(
+        !counters.is_empty(),
+        "emulate_counters needs at least one counter"
+    );
+    thread::scope(|s| {
+        for i in 0..threads {
+            s.spawn(move || {
+                for _ in 0..iterations {
+                    counters.increment(i % counters.len());
+                }
+            });
+        }
+    });
+}
+
+pub fn emulate_sharded(counter: &ShardedCounter, threads: usize, iterations: u64) {
+    thread::scope(|s| {
+        for i in 0..threads {
+            s.spawn(move || {
+                for _ in 0..iterations {
+                    counter.add(i, 1);
+                }
+            });
+        }
+    });
+}
+
+#[test]
+fn test_counters_layout() {
+    assert_eq!(std::mem::size_of::<Counters<8>>(), 8 * 64);
+    assert_eq!(std::mem::size_of::<PackedCounters<8>>(), 8 * 8);
+}
+
+#[test]
+fn test_emulate_counters() {
+    let counters: Counters<4> = Counters::new();
+    emulate_counters(&counters, 6, 1000);
+    let totals: Vec<_> = (0..4).map(|i| counters.get(i)).collect();
+    assert_eq!(totals, [2000, 2000, 1000, 1000]);
+
+    let packed: PackedCounters<8> = PackedCounters::new();
+    emulate_counters(&packed, 3, 1000);
+    let totals: Vec<_> = (0..8).map(|i| packed.get(i)).collect();
+    assert_eq!(totals, [1000, 1000, 1000, 0, 0, 0, 0, 0]);
+}
+
+#[test]
+fn test_sharded_counter() {
+    let counter = ShardedCounter::new(4);
+    emulate_sharded(&counter, 8, 1000);
+    assert_eq!(counter.sum(), 8000);
+    assert_eq!(counter.shards(), 4);
+}
+
+#[test]
+#[should_panic(expected = "emulate_counters needs at least one counter")]
+fn test_emulate_zero_counters() {
+    emulate_counters(&Counters::<0>::new(), 4, 10);
+}
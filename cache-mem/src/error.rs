@@ -0,0 +1,30 @@
+use std::{error::Error, fmt::Display};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MatrixError {
+    DimensionMismatch {
+        lhs: (usize, usize),
+        rhs: (usize, usize),
+    },
+    LengthMismatch {
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl Error for MatrixError {}
+
+impl Display for MatrixError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MatrixError::DimensionMismatch { lhs, rhs } => write!(
+                f,
+                "dimension mismatch: {}x{} and {}x{}",
+                lhs.0, lhs.1, rhs.0, rhs.1
+            ),
+            MatrixError::LengthMismatch { expected, found } => {
+                write!(f, "expected {expected} elements, found {found}")
+            }
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use std::ops::{Index, IndexMut};
+
+use crate::Matrix;
+
+// Maps a logical `(row, col)` to a slot in the backing vector. The same
+// workload run over different layouts touches the same logical cells, only
+// the order they sit in memory changes.
+pub trait Layout: Copy {
+    fn name(&self) -> String;
+    // slots the backing vector needs, may include padding
+    fn storage_len(&self, rows: usize, cols: usize) -> usize;
+    fn offset(&self, rows: usize, cols: usize, row: usize, col: usize) -> usize;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RowMajor;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ColumnMajor;
+
+// Morton order: interleave the bits of row and column, so every aligned
+// power-of-two square is contiguous. The grid is padded to a square with a
+// power-of-two side.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ZOrder;
+
+// `tile`×`tile` blocks stored row-major, blocks themselves in row-major
+// order, the grid is padded to whole tiles
+#[derive(Debug, Clone, Copy)]
+pub struct Tiled {
+    pub tile: usize,
+}
+
+impl Tiled {
+    pub fn new(tile: usize) -> Self {
+        assert!(tile > 0, "tile size must be positive");
+        Self { tile }
+    }
+}
+
+impl Layout for RowMajor {
+    fn name(&self) -> String {
+        "row_major".to_string()
+    }
+
+    fn storage_len(&self, rows: usize, cols: usize) -> usize {
+        rows * cols
+    }
+
+    fn offset(&self, _rows: usize, cols: usize, row: usize, col: usize) -> usize {
+        row * cols + col
+    }
+}
+
+impl Layout for ColumnMajor {
+    fn name(&self) -> String {
+        "column_major".to_string()
+    }
+
+    fn storage_len(&self, rows: usize, cols: usize) -> usize {
+        rows * cols
+    }
+
+    fn offset(&self, rows: usize, _cols: usize, row: usize, col: usize) -> usize {
+        col * rows + row
+    }
+}
+
+// spread the low 32 bits of `x` to the even bit positions
+fn spread_bits(x: u32) -> u64 {
+    let mut x = x as u64;
+    x = (x | (x << 16)) & 0x0000_ffff_0000_ffff;
+    x = (x | (x << 8)) & 0x00ff_00ff_00ff_00ff;
+    x = (x | (x << 4)) & 0x0f0f_0f0f_0f0f_0f0f;
+    x = (x | (x << 2)) & 0x3333_3333_3333_3333;
+    x = (x | (x << 1)) & 0x5555_5555_5555_5555;
+    x
+}
+
+impl Layout for ZOrder {
+    fn name(&self) -> String {
+        "z_order".to_string()
+    }
+
+    fn storage_len(&self, rows: usize, cols: usize) -> usize {
+        let side = rows.max(cols).next_power_of_two();
+        if rows == 0 || cols == 0 {
+            0
+        } else {
+            side * side
+        }
+    }
+
+    fn offset(&self, _rows: usize, _cols: usize, row: usize, col: usize) -> usize {
+        (spread_bits(col as u32) | (spread_bits(row as u32) << 1)) as usize
+    }
+}
+
+impl Layout for Tiled {
+    fn name(&self) -> String {
+        format!("tiled_{}", self.tile)
+    }
+
+    fn storage_len(&self, rows: usize, cols: usize) -> usize {
+        rows.div_ceil(self.tile) * cols.div_ceil(self.tile) * self.tile * self.tile
+    }
+
+    fn offset(&self, _rows: usize, cols: usize, row: usize, col: usize) -> usize {
+        let t = self.tile;
+        let tiles_per_row = cols.div_ceil(t);
+        ((row / t) * tiles_per_row + col / t) * t * t + (row % t) * t + col % t
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Grid<L, T = f64> {
+    rows: usize,
+    cols: usize,
+    layout: L,
+    data: Vec<T>,
+}
+
+impl<L: Layout, T> Index<(usize, usize)> for Grid<L, T> {
+    type Output = T;
+
+    fn index(&self, index: (usize, usize)) -> &Self::Output {
+        assert!(
+            index.0 < self.rows && index.1 < self.cols,
+            "index out of bounds"
+        );
+        &self.data[self.layout.offset(self.rows, self.cols, index.0, index.1)]
+    }
+}
+
+impl<L: Layout, T> IndexMut<(usize, usize)> for Grid<L, T> {
+    fn index_mut(&mut self, index: (usize, usize)) -> &mut Self::Output {
+        assert!(
+            index.0 < self.rows && index.1 < self.cols,
+            "index out of bounds"
+        );
+        &mut self.data[self.layout.offset(self.rows, self.cols, index.0, index.1)]
+    }
+}
+
+impl<L: Layout, T: Copy + Default> Grid<L, T> {
+    pub fn from_fn(
+        rows: usize,
+        cols: usize,
+        layout: L,
+        mut f: impl FnMut(usize, usize) -> T,
+    ) -> Self {
+        let mut grid = Self {
+            rows,
+            cols,
+            layout,
+            data: vec![T::default(); layout.storage_len(rows, cols)],
+        };
+        for i in 0..rows {
+            for j in 0..cols {
+                grid[(i, j)] = f(i, j);
+            }
+        }
+        grid
+    }
+
+    pub fn from_matrix(matrix: &Matrix<T>, layout: L) -> Self {
+        Self::from_fn(matrix.rows(), matrix.cols(), layout, |i, j| matrix[(i, j)])
+    }
+
+    pub fn to_matrix(&self) -> Matrix<T> {
+        Matrix::from_fn(self.rows, self.cols, |i, j| self[(i, j)])
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn layout(&self) -> L {
+        self.layout
+    }
+
+    // logical transpose written into the same kind of layout
+    pub fn transpose(&self) -> Self {
+        Self::from_fn(self.cols, self.rows, self.layout, |i, j| self[(j, i)])
+    }
+}
+
+impl<L: Layout> Grid<L> {
+    // visits cells row by row, cheap only when rows are contiguous
+    pub fn row_scan(&self) -> f64 {
+        let mut sum = 0.0;
+        for i in 0..self.rows {
+            for j in 0..self.cols {
+                sum += self[(i, j)];
+            }
+        }
+        sum
+    }
+
+    // visits cells column by column, the mirror image of `row_scan`
+    pub fn col_scan(&self) -> f64 {
+        let mut sum = 0.0;
+        for j in 0..self.cols {
+            for i in 0..self.rows {
+                sum += self[(i, j)];
+            }
+        }
+        sum
+    }
+
+    // 5-point average of every interior cell, border cells are copied; each
+    // step reads one cell above and one below, so it needs two neighbouring
+    // rows in cache at once
+    pub fn stencil(&self) -> Self {
+        let mut out = self.clone();
+        for i in 1..self.rows.saturating_sub(1) {
+            for j in 1..self.cols.saturating_sub(1) {
+                out[(i, j)] = (self[(i, j)]
+                    + self[(i - 1, j)]
+                    + self[(i + 1, j)]
+                    + self[(i, j - 1)]
+                    + self[(i, j + 1)])
+                    / 5.0;
+            }
+        }
+        out
+    }
+}
+
+#[test]
+fn test_offsets_are_a_bijection() {
+    fn check(layout: impl Layout, rows: usize, cols: usize) {
+        let mut seen = vec![false; layout.storage_len(rows, cols)];
+        for i in 0..rows {
+            for j in 0..cols {
+                let offset = layout.offset(rows, cols, i, j);
+                assert!(!seen[offset], "{} reuses slot {offset}", layout.name());
+                seen[offset] = true;
+            }
+        }
+    }
+    for (rows, cols) in [(1, 1), (4, 4), (5, 3), (3, 17), (16, 9)] {
+        check(RowMajor, rows, cols);
+        check(ColumnMajor, rows, cols);
+        check(ZOrder, rows, cols);
+        check(Tiled::new(4), rows, cols);
+    }
+    assert_eq!(ZOrder.offset(4, 4, 1, 0), 2);
+    assert_eq!(ZOrder.offset(4, 4, 1, 1), 3);
+    assert_eq!(ZOrder.offset(4, 4, 0, 2), 4);
+}
+
+#[test]
+fn test_workloads_agree_across_layouts() {
+    let matrix = Matrix::from_fn(13, 7, |i, j| (i * 7 + j) as f64);
+    let expected_sum: f64 = matrix.as_slice().iter().sum();
+
+    fn check(matrix: &Matrix, layout: impl Layout, expected_sum: f64) {
+        let grid = Grid::from_matrix(matrix, layout);
+        assert_eq!(grid.row_scan(), expected_sum);
+        assert_eq!(grid.col_scan(), expected_sum);
+        assert_eq!(grid.transpose().to_matrix(), matrix.transpose());
+        let reference = Grid::from_matrix(matrix, RowMajor).stencil().to_matrix();
+        assert_eq!(grid.stencil().to_matrix(), reference);
+    }
+    check(&matrix, RowMajor, expected_sum);
+    check(&matrix, ColumnMajor, expected_sum);
+    check(&matrix, ZOrder, expected_sum);
+    check(&matrix, Tiled::new(4), expected_sum);
+}
@@ -0,0 +1,620 @@
+use std::{
+    ops::{Add, AddAssign, Index, IndexMut, Mul, MulAssign, Sub, SubAssign},
+    thread,
+};
+
+use rand::{thread_rng, Rng};
+
+use crate::error::MatrixError;
+
+pub trait Element:
+    Copy + PartialEq + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self>
+{
+    fn zero() -> Self;
+    fn one() -> Self;
+}
+
+macro_rules! impl_element {
+    ($($t: ty),*) => {
+        $(impl Element for $t {
+            fn zero() -> Self {
+                0 as $t
+            }
+            fn one() -> Self {
+                1 as $t
+            }
+        })*
+    };
+}
+
+impl_element!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64);
+
+// row-major `rows`×`cols` matrix
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix<T = f64> {
+    rows: usize,
+    cols: usize,
+    data: Vec<T>,
+}
+
+impl<T> Index<(usize, usize)> for Matrix<T> {
+    type Output = T;
+
+    fn index(&self, index: (usize, usize)) -> &Self::Output {
+        assert!(index.1 < self.cols, "column index out of bounds");
+        &self.data[index.0 * self.cols + index.1]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Matrix<T> {
+    fn index_mut(&mut self, index: (usize, usize)) -> &mut Self::Output {
+        assert!(index.1 < self.cols, "column index out of bounds");
+        &mut self.data[index.0 * self.cols + index.1]
+    }
+}
+
+// a strided view over one column
+#[derive(Debug, Clone, Copy)]
+pub struct Column<'a, T> {
+    data: &'a [T],
+    stride: usize,
+    col: usize,
+    len: usize,
+}
+
+impl<'a, T> Column<'a, T> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &'a T> {
+        self.data
+            .iter()
+            .skip(self.col)
+            .step_by(self.stride.max(1))
+            .take(self.len)
+    }
+}
+
+impl<T> Index<usize> for Column<'_, T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        assert!(index < self.len, "row index out of bounds");
+        &self.data[index * self.stride + self.col]
+    }
+}
+
+impl<T> Matrix<T> {
+    pub fn from_vec(rows: usize, cols: usize, data: Vec<T>) -> Result<Self, MatrixError> {
+        if data.len() != rows * cols {
+            return Err(MatrixError::LengthMismatch {
+                expected: rows * cols,
+                found: data.len(),
+            });
+        }
+        Ok(Self { rows, cols, data })
+    }
+
+    pub fn from_fn(rows: usize, cols: usize, mut f: impl FnMut(usize, usize) -> T) -> Self {
+        let mut data = Vec::with_capacity(rows * cols);
+        for i in 0..rows {
+            for j in 0..cols {
+                data.push(f(i, j));
+            }
+        }
+        Self { rows, cols, data }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn shape(&self) -> (usize, usize) {
+        (self.rows, self.cols)
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.data
+    }
+
+    pub fn row(&self, i: usize) -> &[T] {
+        &self.data[i * self.cols..(i + 1) * self.cols]
+    }
+
+    pub fn row_mut(&mut self, i: usize) -> &mut [T] {
+        &mut self.data[i * self.cols..(i + 1) * self.cols]
+    }
+
+    pub fn col(&self, j: usize) -> Column<'_, T> {
+        assert!(j < self.cols, "column index out of bounds");
+        Column {
+            data: &self.data,
+            stride: self.cols,
+            col: j,
+            len: self.rows,
+        }
+    }
+
+    fn check_same_shape(&self, rhs: &Self) -> Result<(), MatrixError> {
+        if self.shape() != rhs.shape() {
+            return Err(MatrixError::DimensionMismatch {
+                lhs: self.shape(),
+                rhs: rhs.shape(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<T: Copy> Matrix<T> {
+    pub fn transpose(&self) -> Self {
+        let mut data = Vec::with_capacity(self.rows * self.cols);
+        for j in 0..self.cols {
+            for i in 0..self.rows {
+                data.push(self[(i, j)]);
+            }
+        }
+        Self {
+            rows: self.cols,
+            cols: self.rows,
+            data,
+        }
+    }
+
+    // Cache-oblivious: keep halving the larger side of the block until it is
+    // small enough, so some level of the recursion fits every cache level.
+    pub fn transpose_recursive(&self) -> Self {
+        let mut data = self.data.clone();
+        let shape = self.shape();
+        transpose_block(&self.data, &mut data, shape, (0, self.rows), (0, self.cols));
+        Self {
+            rows: self.cols,
+            cols: self.rows,
+            data,
+        }
+    }
+}
+
+impl<T: Element> Matrix<T> {
+    pub fn zeros(rows: usize, cols: usize) -> Self {
+        Self {
+            rows,
+            cols,
+            data: vec![T::zero(); rows * cols],
+        }
+    }
+
+    pub fn identity(n: usize) -> Self {
+        Self::from_fn(n, n, |i, j| if i == j { T::one() } else { T::zero() })
+    }
+
+    pub fn checked_add(&self, rhs: &Self) -> Result<Self, MatrixError> {
+        self.check_same_shape(rhs)?;
+        Ok(self.zip_with(rhs, |a, b| a + b))
+    }
+
+    pub fn checked_sub(&self, rhs: &Self) -> Result<Self, MatrixError> {
+        self.check_same_shape(rhs)?;
+        Ok(self.zip_with(rhs, |a, b| a - b))
+    }
+
+    fn zip_with(&self, rhs: &Self, f: impl Fn(T, T) -> T) -> Self {
+        Self {
+            rows: self.rows,
+            cols: self.cols,
+            data: self
+                .data
+                .iter()
+                .zip(&rhs.data)
+                .map(|(&a, &b)| f(a, b))
+                .collect(),
+        }
+    }
+
+    pub fn scale(&self, k: T) -> Self {
+        Self {
+            rows: self.rows,
+            cols: self.cols,
+            data: self.data.iter().map(|&a| a * k).collect(),
+        }
+    }
+
+    // generic i-k-j product, see `mul_matrix` and friends for the f64 fast paths
+    pub fn mul(&self, rhs: &Self) -> Result<Self, MatrixError> {
+        if self.cols != rhs.rows {
+            return Err(MatrixError::DimensionMismatch {
+                lhs: self.shape(),
+                rhs: rhs.shape(),
+            });
+        }
+        let mut out = Self::zeros(self.rows, rhs.cols);
+        for i in 0..self.rows {
+            for k in 0..self.cols {
+                let a = self.data[i * self.cols + k];
+                for (o, &b) in out.row_mut(i).iter_mut().zip(rhs.row(k)) {
+                    *o = *o + a * b;
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+impl<T: Element> Add for &Matrix<T> {
+    type Output = Matrix<T>;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        self.checked_add(rhs).expect("matrix add")
+    }
+}
+
+impl<T: Element> Sub for &Matrix<T> {
+    type Output = Matrix<T>;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.checked_sub(rhs).expect("matrix sub")
+    }
+}
+
+impl<T: Element> Mul<T> for &Matrix<T> {
+    type Output = Matrix<T>;
+
+    fn mul(self, rhs: T) -> Self::Output {
+        self.scale(rhs)
+    }
+}
+
+impl<T: Element> AddAssign<&Matrix<T>> for Matrix<T> {
+    fn add_assign(&mut self, rhs: &Matrix<T>) {
+        self.check_same_shape(rhs).expect("matrix add");
+        for (a, &b) in self.data.iter_mut().zip(&rhs.data) {
+            *a = *a + b;
+        }
+    }
+}
+
+impl<T: Element> SubAssign<&Matrix<T>> for Matrix<T> {
+    fn sub_assign(&mut self, rhs: &Matrix<T>) {
+        self.check_same_shape(rhs).expect("matrix sub");
+        for (a, &b) in self.data.iter_mut().zip(&rhs.data) {
+            *a = *a - b;
+        }
+    }
+}
+
+impl<T: Element> MulAssign<T> for Matrix<T> {
+    fn mul_assign(&mut self, rhs: T) {
+        for a in self.data.iter_mut() {
+            *a = *a * rhs;
+        }
+    }
+}
+
+impl Matrix<f64> {
+    pub fn from_random(n: usize) -> Self {
+        let mut rng = thread_rng();
+        let mut result = Vec::with_capacity(n * n);
+        for _ in 0..n {
+            for _ in 0..n {
+                result.push(rng.gen());
+            }
+        }
+        Self {
+            rows: n,
+            cols: n,
+            data: result,
+        }
+    }
+
+    fn assert_mul_shape(&self, rhs: &Self) {
+        assert_eq!(self.cols, rhs.rows, "dimension mismatch");
+    }
+
+    pub fn mul_matrix(&self, rhs: &Self) -> Self {
+        self.assert_mul_shape(rhs);
+        let rhs = rhs.transpose();
+        let mut data = Vec::with_capacity(self.rows * rhs.rows);
+        for i in 0..self.rows {
+            for j in 0..rhs.rows {
+                let mut result = 0.0;
+                for k in 0..self.cols {
+                    result += self[(i, k)] * rhs[(j, k)];
+                }
+                data.push(result);
+            }
+        }
+        Self {
+            rows: self.rows,
+            cols: rhs.rows,
+            data,
+        }
+    }
+
+    // Same arithmetic as `mul_matrix`, with the output rows split into one
+    // contiguous band per scoped thread, so no two threads write the same slot.
+    pub fn par_mul_matrix(&self, rhs: &Self, threads: usize) -> Self {
+        self.assert_mul_shape(rhs);
+        assert!(threads > 0, "need at least one thread");
+        let (rows, cols, inner) = (self.rows, rhs.cols, self.cols);
+        let rhs = rhs.transpose_recursive();
+        let mut data = vec![0.0; rows * cols];
+        if rows == 0 || cols == 0 {
+            return Self { rows, cols, data };
+        }
+        let rows_per_thread = rows.div_ceil(threads);
+        thread::scope(|s| {
+            for (band, out) in data.chunks_mut(rows_per_thread * cols).enumerate() {
+                let rhs = &rhs;
+                s.spawn(move || {
+                    let first_row = band * rows_per_thread;
+                    for (r, out_row) in out.chunks_exact_mut(cols).enumerate() {
+                        let lhs_row = self.row(first_row + r);
+                        for (j, o) in out_row.iter_mut().enumerate() {
+                            let rhs_row = rhs.row(j);
+                            let mut result = 0.0;
+                            for k in 0..inner {
+                                result += lhs_row[k] * rhs_row[k];
+                            }
+                            *o = result;
+                        }
+                    }
+                });
+            }
+        });
+        Self { rows, cols, data }
+    }
+
+    // i-k-j order: the inner loop walks a row of `rhs` and a row of the
+    // result, both contiguous, instead of striding down a column
+    pub fn mul_matrix_ikj(&self, rhs: &Self) -> Self {
+        self.mul_rows(rhs, axpy_scalar)
+    }
+
+    // i-k-j order inside `tile`×`tile` blocks so the working set of all three
+    // matrices stays in cache while a block is being multiplied
+    pub fn mul_matrix_tiled(&self, rhs: &Self, tile: usize) -> Self {
+        self.assert_mul_shape(rhs);
+        assert!(tile > 0, "tile size must be positive");
+        let (rows, cols, inner) = (self.rows, rhs.cols, self.cols);
+        let mut data = vec![0.0; rows * cols];
+        for ii in (0..rows).step_by(tile) {
+            for kk in (0..inner).step_by(tile) {
+                for jj in (0..cols).step_by(tile) {
+                    let j_end = (jj + tile).min(cols);
+                    for i in ii..(ii + tile).min(rows) {
+                        let out = &mut data[i * cols + jj..i * cols + j_end];
+                        for k in kk..(kk + tile).min(inner) {
+                            axpy_scalar(
+                                out,
+                                self.data[i * inner + k],
+                                &rhs.data[k * cols + jj..k * cols + j_end],
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        Self { rows, cols, data }
+    }
+
+    // i-k-j order with an explicitly vectorized inner loop when the CPU
+    // supports it, FMA rounding means results differ from `mul_matrix` by ulps
+    pub fn mul_matrix_simd(&self, rhs: &Self) -> Self {
+        self.mul_rows(rhs, axpy_kernel())
+    }
+
+    fn mul_rows(&self, rhs: &Self, axpy: fn(&mut [f64], f64, &[f64])) -> Self {
+        self.assert_mul_shape(rhs);
+        let (rows, cols) = (self.rows, rhs.cols);
+        let mut data = vec![0.0; rows * cols];
+        if cols == 0 {
+            return Self { rows, cols, data };
+        }
+        for (i, out) in data.chunks_exact_mut(cols).enumerate() {
+            for (&a, rhs_row) in self.row(i).iter().zip(rhs.data.chunks_exact(cols)) {
+                axpy(out, a, rhs_row);
+            }
+        }
+        Self { rows, cols, data }
+    }
+
+    pub fn approx_eq(&self, other: &Self, epsilon: f64) -> bool {
+        self.shape() == other.shape()
+            && self
+                .data
+                .iter()
+                .zip(&other.data)
+                .all(|(a, b)| (a - b).abs() <= epsilon * a.abs().max(b.abs()).max(1.0))
+    }
+}
+
+const TRANSPOSE_BLOCK: usize = 16;
+
+// `src` is `(src_rows, src_cols)`, `dst` its transpose
+fn transpose_block<T: Copy>(
+    src: &[T],
+    dst: &mut [T],
+    (src_rows, src_cols): (usize, usize),
+    (r0, r1): (usize, usize),
+    (c0, c1): (usize, usize),
+) {
+    let (rows, cols) = (r1 - r0, c1 - c0);
+    if rows <= TRANSPOSE_BLOCK && cols <= TRANSPOSE_BLOCK {
+        for i in r0..r1 {
+            for j in c0..c1 {
+                dst[j * src_rows + i] = src[i * src_cols + j];
+            }
+        }
+    } else if rows >= cols {
+        let mid = r0 + rows / 2;
+        let shape = (src_rows, src_cols);
+        transpose_block(src, dst, shape, (r0, mid), (c0, c1));
+        transpose_block(src, dst, shape, (mid, r1), (c0, c1));
+    } else {
+        let mid = c0 + cols / 2;
+        let shape = (src_rows, src_cols);
+        transpose_block(src, dst, shape, (r0, r1), (c0, mid));
+        transpose_block(src, dst, shape, (r0, r1), (mid, c1));
+    }
+}
+
+// out += a * x
+fn axpy_scalar(out: &mut [f64], a: f64, x: &[f64]) {
+    for (o, &x) in out.iter_mut().zip(x) {
+        *o += a * x;
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,fma")]
+unsafe fn axpy_avx2(out: &mut [f64], a: f64, x: &[f64]) {
+    use std::arch::x86_64::{_mm256_fmadd_pd, _mm256_loadu_pd, _mm256_set1_pd, _mm256_storeu_pd};
+
+    let n = out.len().min(x.len());
+    let va = _mm256_set1_pd(a);
+    let mut j = 0;
+    while j + 4 <= n {
+        let vo = _mm256_loadu_pd(out.as_ptr().add(j));
+        let vx = _mm256_loadu_pd(x.as_ptr().add(j));
+        _mm256_storeu_pd(out.as_mut_ptr().add(j), _mm256_fmadd_pd(va, vx, vo));
+        j += 4;
+    }
+    axpy_scalar(&mut out[j..n], a, &x[j..n]);
+}
+
+fn axpy_kernel() -> fn(&mut [f64], f64, &[f64]) {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma") {
+        // SAFETY: only picked after checking the CPU supports avx2 and fma
+        return |out, a, x| unsafe { axpy_avx2(out, a, x) };
+    }
+    axpy_scalar
+}
+
+#[test]
+fn test_mul_matrix() {
+    let data = vec![1.0, 2.0, 3.0, 4.0];
+    let lhs = Matrix::from_vec(2, 2, data).unwrap();
+    let data = vec![5.0, 6.0, 7.0, 8.0];
+    let rhs = Matrix::from_vec(2, 2, data).unwrap();
+    let data = vec![19.0, 22.0, 43.0, 50.0];
+    let expected = Matrix::from_vec(2, 2, data).unwrap();
+    assert_eq!(lhs.mul_matrix(&rhs), expected);
+}
+
+#[test]
+fn test_mul_matrix_variants() {
+    for n in [0, 1, 7, 33, 64] {
+        let lhs = Matrix::from_random(n);
+        let rhs = Matrix::from_random(n);
+        let expected = lhs.mul_matrix(&rhs);
+        assert!(lhs.mul_matrix_ikj(&rhs).approx_eq(&expected, 1e-12));
+        assert!(lhs.mul_matrix_simd(&rhs).approx_eq(&expected, 1e-12));
+        for tile in [1, 8, 16, 100] {
+            assert!(lhs.mul_matrix_tiled(&rhs, tile).approx_eq(&expected, 1e-12));
+        }
+    }
+}
+
+#[test]
+fn test_axpy_kernels_agree() {
+    let x: Vec<f64> = (0..11).map(|i| i as f64 * 0.5).collect();
+    let mut scalar = vec![1.0; 11];
+    let mut simd = vec![1.0; 11];
+    axpy_scalar(&mut scalar, 3.0, &x);
+    axpy_kernel()(&mut simd, 3.0, &x);
+    assert_eq!(scalar, simd);
+    assert_eq!(scalar[10], 16.0);
+}
+
+#[test]
+fn test_transpose_recursive() {
+    for n in [0, 1, 15, 16, 17, 100] {
+        let m = Matrix::from_random(n);
+        assert_eq!(m.transpose_recursive(), m.transpose());
+    }
+}
+
+#[test]
+fn test_par_mul_matrix_exact() {
+    for n in [0, 1, 5, 31, 64] {
+        let lhs = Matrix::from_random(n);
+        let rhs = Matrix::from_random(n);
+        let expected = lhs.mul_matrix(&rhs);
+        for threads in [1, 2, 3, 8, 100] {
+            assert_eq!(lhs.par_mul_matrix(&rhs, threads), expected);
+        }
+    }
+}
+
+#[test]
+fn test_constructors() {
+    let m: Matrix<i32> = Matrix::from_fn(2, 3, |i, j| (i * 10 + j) as i32);
+    assert_eq!(m.shape(), (2, 3));
+    assert_eq!(m.row(1), [10, 11, 12]);
+    assert_eq!(m.col(2).iter().copied().collect::<Vec<_>>(), [2, 12]);
+    assert_eq!(m.col(1)[1], 11);
+    assert_eq!(Matrix::<u8>::zeros(2, 2).into_vec(), [0; 4]);
+    assert_eq!(Matrix::<i64>::identity(2).into_vec(), [1, 0, 0, 1]);
+    assert_eq!(
+        Matrix::from_vec(2, 2, vec![1, 2, 3]).unwrap_err(),
+        MatrixError::LengthMismatch {
+            expected: 4,
+            found: 3
+        }
+    );
+}
+
+#[test]
+fn test_rectangular_ops() {
+    let mut a = Matrix::from_vec(2, 3, vec![1, 2, 3, 4, 5, 6]).unwrap();
+    let b = Matrix::from_vec(3, 2, vec![7, 8, 9, 10, 11, 12]).unwrap();
+    let product = a.mul(&b).unwrap();
+    assert_eq!(
+        product,
+        Matrix::from_vec(2, 2, vec![58, 64, 139, 154]).unwrap()
+    );
+    assert_eq!(
+        a.mul(&a).unwrap_err(),
+        MatrixError::DimensionMismatch {
+            lhs: (2, 3),
+            rhs: (2, 3)
+        }
+    );
+    assert_eq!((&a + &a).into_vec(), [2, 4, 6, 8, 10, 12]);
+    assert_eq!((&a - &a), Matrix::zeros(2, 3));
+    assert_eq!((&a * 3).row(0), [3, 6, 9]);
+    assert!(a.checked_add(&b).is_err());
+    a[(1, 2)] = 60;
+    a.row_mut(0)[0] = 10;
+    a *= 2;
+    a -= &Matrix::from_fn(2, 3, |i, j| (i + j) as i32);
+    assert_eq!(a.into_vec(), [20, 3, 4, 7, 8, 117]);
+    assert_eq!(Matrix::<i32>::identity(3).mul(&b).unwrap(), b);
+    assert_eq!(b.transpose_recursive(), b.transpose());
+}
+
+#[test]
+fn test_rectangular_fast_paths() {
+    let lhs = Matrix::from_fn(5, 3, |i, j| (i + 2 * j) as f64);
+    let rhs = Matrix::from_fn(3, 4, |i, j| (i * j) as f64 + 0.5);
+    let expected = lhs.mul(&rhs).unwrap();
+    assert!(lhs.mul_matrix(&rhs).approx_eq(&expected, 1e-12));
+    assert!(lhs.mul_matrix_ikj(&rhs).approx_eq(&expected, 1e-12));
+    assert!(lhs.mul_matrix_simd(&rhs).approx_eq(&expected, 1e-12));
+    assert!(lhs.mul_matrix_tiled(&rhs, 2).approx_eq(&expected, 1e-12));
+    assert_eq!(lhs.par_mul_matrix(&rhs, 2), lhs.mul_matrix(&rhs));
+}
@@ -0,0 +1,283 @@
+use rand::{thread_rng, Rng};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Particle {
+    pub position: [f64; 3],
+    pub velocity: [f64; 3],
+    pub mass: f64,
+}
+
+pub fn random_particles(n: usize) -> Vec<Particle> {
+    let mut rng = thread_rng();
+    (0..n)
+        .map(|_| Particle {
+            position: rng.gen(),
+            velocity: rng.gen(),
+            mass: rng.gen_range(0.5..2.0),
+        })
+        .collect()
+}
+
+// Shared API of both stores. Every method does the same arithmetic in the
+// same particle order, so the two layouts give bit-identical results and only
+// the memory traffic differs.
+pub trait ParticleStore {
+    fn from_particles(particles: &[Particle]) -> Self;
+    fn len(&self) -> usize;
+    fn get(&self, i: usize) -> Particle;
+    // explicit Euler step under constant `gravity` along z
+    fn update(&mut self, dt: f64, gravity: f64);
+    // touches only velocities and masses
+    fn kinetic_energy(&self) -> f64;
+    // touches only positions and masses
+    fn center_of_mass(&self) -> [f64; 3];
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// one 56 byte struct per particle, a pass over velocities drags positions
+// through the cache as well
+#[derive(Debug, Clone, Default)]
+pub struct AosParticles(Vec<Particle>);
+
+// one vector per field, a pass only loads the fields it reads
+#[derive(Debug, Clone, Default)]
+pub struct SoaParticles {
+    x: Vec<f64>,
+    y: Vec<f64>,
+    z: Vec<f64>,
+    vx: Vec<f64>,
+    vy: Vec<f64>,
+    vz: Vec<f64>,
+    mass: Vec<f64>,
+}
+
+impl ParticleStore for AosParticles {
+    fn from_particles(particles: &[Particle]) -> Self {
+        Self(particles.to_vec())
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn get(&self, i: usize) -> Particle {
+        self.0[i]
+    }
+
+    fn update(&mut self, dt: f64, gravity: f64) {
+        for p in &mut self.0 {
+            p.velocity[2] += gravity * dt;
+            for (x, v) in p.position.iter_mut().zip(p.velocity) {
+                *x += v * dt;
+            }
+        }
+    }
+
+    fn kinetic_energy(&self) -> f64 {
+        self.0
+            .iter()
+            .map(|p| {
+                let [vx, vy, vz] = p.velocity;
+                0.5 * p.mass * (vx * vx + vy * vy + vz * vz)
+            })
+            .sum()
+    }
+
+    fn center_of_mass(&self) -> [f64; 3] {
+        let mut weighted = [0.0; 3];
+        let mut total = 0.0;
+        for p in &self.0 {
+            for (w, x) in weighted.iter_mut().zip(p.position) {
+                *w += p.mass * x;
+            }
+            total += p.mass;
+        }
+        weighted.map(|w| w / total)
+    }
+}
+
+impl ParticleStore for SoaParticles {
+    fn from_particles(particles: &[Particle]) -> Self {
+        let field = |f: fn(&Particle) -> f64| particles.iter().map(f).collect();
+        Self {
+            x: field(|p| p.position[0]),
+            y: field(|p| p.position[1]),
+            z: field(|p| p.position[2]),
+            vx: field(|p| p.velocity[0]),
+            vy: field(|p| p.velocity[1]),
+            vz: field(|p| p.velocity[2]),
+            mass: field(|p| p.mass),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.mass.len()
+    }
+
+    fn get(&self, i: usize) -> Particle {
+        Particle {
+            position: [self.x[i], self.y[i], self.z[i]],
+            velocity: [self.vx[i], self.vy[i], self.vz[i]],
+            mass: self.mass[i],
+        }
+    }
+
+    fn update(&mut self, dt: f64, gravity: f64) {
+        for vz in &mut self.vz {
+            *vz += gravity * dt;
+        }
+        for (pos, vel) in [
+            (&mut self.x, &self.vx),
+            (&mut self.y, &self.vy),
+            (&mut self.z, &self.vz),
+        ] {
+            for (p, v) in pos.iter_mut().zip(vel) {
+                *p += v * dt;
+            }
+        }
+    }
+
+    fn kinetic_energy(&self) -> f64 {
+        let mut energy = 0.0;
+        for i in 0..self.len() {
+            let (vx, vy, vz) = (self.vx[i], self.vy[i], self.vz[i]);
+            energy += 0.5 * self.mass[i] * (vx * vx + vy * vy + vz * vz);
+        }
+        energy
+    }
+
+    fn center_of_mass(&self) -> [f64; 3] {
+        let mut weighted = [0.0; 3];
+        let mut total = 0.0;
+        for i in 0..self.len() {
+            let m = self.mass[i];
+            weighted[0] += m * self.x[i];
+            weighted[1] += m * self.y[i];
+            weighted[2] += m * self.z[i];
+            total += m;
+        }
+        weighted.map(|w| w / total)
+    }
+}
+
+// A particle as it often ends up in real code: the fields the simulation
+// step touches every frame sit next to bookkeeping that is read rarely, so
+// every cache line loaded for an update is mostly cold bytes.
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub struct FatParticle {
+    pub position: [f32; 3],
+    pub id: u64,
+    pub name: [u8; 32],
+    pub velocity: [f32; 3],
+    pub color: [u8; 4],
+    pub spawn_time: f64,
+    pub flags: u32,
+}
+
+// hot half of `FatParticle`, 24 bytes, so 8 of them fill three cache lines
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[repr(C)]
+pub struct HotParticle {
+    pub position: [f32; 3],
+    pub velocity: [f32; 3],
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub struct ColdParticle {
+    pub id: u64,
+    pub spawn_time: f64,
+    pub name: [u8; 32],
+    pub color: [u8; 4],
+    pub flags: u32,
+}
+
+// same data as a `Vec<FatParticle>` with the hot fields split off, the cold
+// vector is indexed in parallel
+#[derive(Debug, Clone, Default)]
+pub struct SplitParticles {
+    pub hot: Vec<HotParticle>,
+    pub cold: Vec<ColdParticle>,
+}
+
+impl From<&[FatParticle]> for SplitParticles {
+    fn from(particles: &[FatParticle]) -> Self {
+        let hot = particles
+            .iter()
+            .map(|p| HotParticle {
+                position: p.position,
+                velocity: p.velocity,
+            })
+            .collect();
+        let cold = particles
+            .iter()
+            .map(|p| ColdParticle {
+                id: p.id,
+                spawn_time: p.spawn_time,
+                name: p.name,
+                color: p.color,
+                flags: p.flags,
+            })
+            .collect();
+        Self { hot, cold }
+    }
+}
+
+pub fn step_fat(particles: &mut [FatParticle], dt: f32) {
+    for p in particles {
+        for (x, v) in p.position.iter_mut().zip(p.velocity) {
+            *x += v * dt;
+        }
+    }
+}
+
+pub fn step_hot(particles: &mut [HotParticle], dt: f32) {
+    for p in particles {
+        for (x, v) in p.position.iter_mut().zip(p.velocity) {
+            *x += v * dt;
+        }
+    }
+}
+
+#[test]
+fn test_layouts_agree() {
+    let particles = random_particles(1000);
+    let mut aos = AosParticles::from_particles(&particles);
+    let mut soa = SoaParticles::from_particles(&particles);
+    for _ in 0..10 {
+        aos.update(0.01, -9.81);
+        soa.update(0.01, -9.81);
+    }
+    assert_eq!(aos.len(), soa.len());
+    for i in 0..aos.len() {
+        assert_eq!(aos.get(i), soa.get(i));
+    }
+    assert_eq!(aos.kinetic_energy(), soa.kinetic_energy());
+    assert_eq!(aos.center_of_mass(), soa.center_of_mass());
+}
+
+#[test]
+fn test_hot_cold_split() {
+    assert_eq!(std::mem::size_of::<FatParticle>(), 88);
+    assert_eq!(std::mem::size_of::<HotParticle>(), 24);
+
+    let mut fat: Vec<_> = (0..100)
+        .map(|i| FatParticle {
+            position: [i as f32, 0.0, 1.0],
+            velocity: [1.0, 2.0, -0.5],
+            id: i,
+            ..Default::default()
+        })
+        .collect();
+    let mut split = SplitParticles::from(fat.as_slice());
+    step_fat(&mut fat, 0.1);
+    step_hot(&mut split.hot, 0.1);
+    for (f, h) in fat.iter().zip(&split.hot) {
+        assert_eq!(f.position, h.position);
+    }
+    assert_eq!(split.cold[42].id, 42);
+}
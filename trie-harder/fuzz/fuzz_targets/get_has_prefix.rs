@@ -0,0 +1,50 @@
+#![no_main]
+
+use std::collections::BTreeMap;
+
+use libfuzzer_sys::fuzz_target;
+use trie_harder::TrieHarderMap;
+
+// Input layout: one count byte, then that many length-prefixed keys, the
+// rest of the input is split into queries the same way.
+fn split(mut data: &[u8]) -> (Vec<&[u8]>, Vec<&[u8]>) {
+    let Some((&count, rest)) = data.split_first() else {
+        return (vec![], vec![]);
+    };
+    data = rest;
+    let mut items = vec![];
+    while let Some((&len, rest)) = data.split_first() {
+        let len = (len as usize).min(rest.len());
+        items.push(&rest[..len]);
+        data = &rest[len..];
+    }
+    let count = (count as usize).min(items.len());
+    let queries = items.split_off(count);
+    (items, queries)
+}
+
+fuzz_target!(|data: &[u8]| {
+    let (keys, queries) = split(data);
+    let mut distinct = [false; 256];
+    keys.iter()
+        .flat_map(|k| k.iter())
+        .for_each(|&c| distinct[c as usize] = true);
+    if distinct.iter().filter(|&&d| d).count() > 128 {
+        return;
+    }
+    let values: Vec<usize> = (0..keys.len()).collect();
+    let th: TrieHarderMap<'_, u128, usize> = TrieHarderMap::from_strs_and_values(&keys, &values);
+    let mut oracle = BTreeMap::new();
+    for (i, &key) in keys.iter().enumerate() {
+        oracle.insert(key, i);
+    }
+    for &query in keys.iter().chain(&queries) {
+        assert_eq!(th.get(query), oracle.get(query));
+        let has_prefix = !oracle.contains_key(query)
+            && oracle
+                .range::<&[u8], _>(query..)
+                .next()
+                .is_some_and(|(k, _)| k.starts_with(query));
+        assert_eq!(th.has_prefix(query), has_prefix);
+    }
+});
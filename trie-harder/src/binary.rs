@@ -0,0 +1,474 @@
+use std::{
+    fmt::Display,
+    io::{self, Write},
+};
+
+use crate::{TrieHarderMap, TrieNode, UnsignedInt};
+
+const MAGIC: &[u8; 4] = b"THRD";
+const VERSION: u16 = 1;
+const HEADER_LEN: usize = 48;
+
+const KIND_BRANCH: u8 = 0;
+const KIND_LEAF: u8 = 1;
+
+// kind + mask + children(start, len) + key(start, len) + value(start, len)
+const fn node_len(width: usize) -> usize {
+    1 + width + 6 * 8
+}
+
+pub trait EncodeValue {
+    fn encode_value(&self, out: &mut Vec<u8>);
+}
+
+pub trait DecodeValue<'a>: Sized {
+    fn decode_value(bytes: &'a [u8]) -> Option<Self>;
+}
+
+impl EncodeValue for () {
+    fn encode_value(&self, _out: &mut Vec<u8>) {}
+}
+
+impl DecodeValue<'_> for () {
+    fn decode_value(bytes: &[u8]) -> Option<Self> {
+        bytes.is_empty().then_some(())
+    }
+}
+
+impl EncodeValue for [u8] {
+    fn encode_value(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self);
+    }
+}
+
+impl EncodeValue for &[u8] {
+    fn encode_value(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self);
+    }
+}
+
+impl<'a> DecodeValue<'a> for &'a [u8] {
+    fn decode_value(bytes: &'a [u8]) -> Option<Self> {
+        Some(bytes)
+    }
+}
+
+impl EncodeValue for Vec<u8> {
+    fn encode_value(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self);
+    }
+}
+
+impl DecodeValue<'_> for Vec<u8> {
+    fn decode_value(bytes: &[u8]) -> Option<Self> {
+        Some(bytes.to_vec())
+    }
+}
+
+impl EncodeValue for &str {
+    fn encode_value(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self.as_bytes());
+    }
+}
+
+impl<'a> DecodeValue<'a> for &'a str {
+    fn decode_value(bytes: &'a [u8]) -> Option<Self> {
+        std::str::from_utf8(bytes).ok()
+    }
+}
+
+impl EncodeValue for String {
+    fn encode_value(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self.as_bytes());
+    }
+}
+
+impl DecodeValue<'_> for String {
+    fn decode_value(bytes: &[u8]) -> Option<Self> {
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
+
+macro_rules! impl_value_codec_int {
+    ($($i: ty),*) => {
+        $(
+            impl EncodeValue for $i {
+                fn encode_value(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+            }
+
+            impl DecodeValue<'_> for $i {
+                fn decode_value(bytes: &[u8]) -> Option<Self> {
+                    Some(<$i>::from_le_bytes(bytes.try_into().ok()?))
+                }
+            }
+        )*
+    };
+}
+
+impl_value_codec_int!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ViewError {
+    TooShort,
+    BadMagic,
+    UnsupportedVersion(u16),
+    UnsupportedMaskWidth(u8),
+    LengthMismatch,
+    ChecksumMismatch,
+    Corrupted(&'static str),
+}
+
+impl std::error::Error for ViewError {}
+
+impl Display for ViewError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ViewError::TooShort => write!(f, "buffer too short for trie header"),
+            ViewError::BadMagic => write!(f, "bad magic number"),
+            ViewError::UnsupportedVersion(v) => write!(f, "unsupported format version {v}"),
+            ViewError::UnsupportedMaskWidth(w) => write!(f, "unsupported mask width {w}"),
+            ViewError::LengthMismatch => write!(f, "section lengths do not match buffer"),
+            ViewError::ChecksumMismatch => write!(f, "checksum mismatch"),
+            ViewError::Corrupted(reason) => write!(f, "corrupted trie: {reason}"),
+        }
+    }
+}
+
+// 64-bit FNV-1a, good enough to catch truncated or bit-flipped files
+fn checksum(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+impl<T, V> TrieHarderMap<'_, T, V>
+where
+    T: UnsignedInt,
+    V: EncodeValue,
+{
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(&self.to_bytes())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let width = std::mem::size_of::<T>();
+        let mut body = Vec::with_capacity(256 * width + self.nodes.len() * node_len(width));
+        for mask in &self.lookup_table.0 {
+            let mask = mask.map(|m| m.to_u128()).unwrap_or(0);
+            body.extend_from_slice(&mask.to_le_bytes()[..width]);
+        }
+        let mut children = vec![];
+        let mut keys = vec![];
+        let mut values = vec![];
+        for node in &self.nodes {
+            let (kind, key) = match node {
+                TrieNode::Branch(_) => (KIND_BRANCH, &[][..]),
+                TrieNode::Leaf(n) => (KIND_LEAF, n.string),
+            };
+            let value_start = values.len();
+            if let TrieNode::Leaf(n) = node {
+                n.value.encode_value(&mut values);
+            }
+            body.push(kind);
+            body.extend_from_slice(&node.mask().to_u128().to_le_bytes()[..width]);
+            for n in [
+                children.len(),
+                node.children().len(),
+                keys.len(),
+                key.len(),
+                value_start,
+                values.len() - value_start,
+            ] {
+                body.extend_from_slice(&(n as u64).to_le_bytes());
+            }
+            children.extend(node.children().iter().map(|&c| c as u64));
+            keys.extend_from_slice(key);
+        }
+        for c in &children {
+            body.extend_from_slice(&c.to_le_bytes());
+        }
+        body.extend_from_slice(&keys);
+        body.extend_from_slice(&values);
+
+        let mut out = Vec::with_capacity(HEADER_LEN + body.len());
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.push(width as u8);
+        out.push(0);
+        for n in [self.nodes.len(), children.len(), keys.len(), values.len()] {
+            out.extend_from_slice(&(n as u64).to_le_bytes());
+        }
+        out.extend_from_slice(&checksum(&body).to_le_bytes());
+        out.extend_from_slice(&body);
+        out
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TrieHarderView<'a> {
+    width: usize,
+    node_count: usize,
+    lookup_table: &'a [u8],
+    nodes: &'a [u8],
+    children: &'a [u8],
+    keys: &'a [u8],
+    values: &'a [u8],
+}
+
+#[derive(Debug, Clone, Copy)]
+struct NodeRecord {
+    kind: u8,
+    mask: u128,
+    children: (usize, usize),
+    key: (usize, usize),
+    value: (usize, usize),
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+fn read_mask(bytes: &[u8], offset: usize, width: usize) -> u128 {
+    let mut buf = [0; 16];
+    buf[..width].copy_from_slice(&bytes[offset..offset + width]);
+    u128::from_le_bytes(buf)
+}
+
+fn in_bounds((start, len): (usize, usize), total: usize) -> bool {
+    start.checked_add(len).is_some_and(|end| end <= total)
+}
+
+impl<'a> TrieHarderView<'a> {
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, ViewError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(ViewError::TooShort);
+        }
+        if &bytes[..4] != MAGIC {
+            return Err(ViewError::BadMagic);
+        }
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version != VERSION {
+            return Err(ViewError::UnsupportedVersion(version));
+        }
+        let width = bytes[6];
+        if !matches!(width, 1 | 2 | 4 | 8 | 16) {
+            return Err(ViewError::UnsupportedMaskWidth(width));
+        }
+        let width = width as usize;
+        let to_usize = |offset| usize::try_from(read_u64(bytes, offset)).ok();
+        let (Some(node_count), Some(children_count), Some(keys_len), Some(values_len)) =
+            (to_usize(8), to_usize(16), to_usize(24), to_usize(32))
+        else {
+            return Err(ViewError::LengthMismatch);
+        };
+        let body = &bytes[HEADER_LEN..];
+        let sections = [
+            Some(256 * width),
+            node_count.checked_mul(node_len(width)),
+            children_count.checked_mul(8),
+            Some(keys_len),
+            Some(values_len),
+        ];
+        let mut lens = [0; 5];
+        let mut total = 0_usize;
+        for (len, section) in lens.iter_mut().zip(sections) {
+            *len = section.ok_or(ViewError::LengthMismatch)?;
+            total = total.checked_add(*len).ok_or(ViewError::LengthMismatch)?;
+        }
+        if total != body.len() {
+            return Err(ViewError::LengthMismatch);
+        }
+        if checksum(body) != read_u64(bytes, 40) {
+            return Err(ViewError::ChecksumMismatch);
+        }
+        let (lookup_table, rest) = body.split_at(lens[0]);
+        let (nodes, rest) = rest.split_at(lens[1]);
+        let (children, rest) = rest.split_at(lens[2]);
+        let (keys, values) = rest.split_at(lens[3]);
+        let view = Self {
+            width,
+            node_count,
+            lookup_table,
+            nodes,
+            children,
+            keys,
+            values,
+        };
+        view.validate(children_count)?;
+        Ok(view)
+    }
+
+    // checks every offset up front so lookups never index out of bounds
+    fn validate(&self, children_count: usize) -> Result<(), ViewError> {
+        if self.node_count == 0 {
+            return Err(ViewError::Corrupted("missing root node"));
+        }
+        if (0..=u8::MAX).any(|c| self.lookup(c).count_ones() > 1) {
+            return Err(ViewError::Corrupted("lookup mask with multiple bits"));
+        }
+        for i in 0..self.node_count {
+            let node = self.node(i);
+            if !matches!(node.kind, KIND_BRANCH | KIND_LEAF) {
+                return Err(ViewError::Corrupted("unknown node kind"));
+            }
+            if node.mask.count_ones() as usize != node.children.1 {
+                return Err(ViewError::Corrupted("mask does not match children"));
+            }
+            if !in_bounds(node.children, children_count)
+                || !in_bounds(node.key, self.keys.len())
+                || !in_bounds(node.value, self.values.len())
+            {
+                return Err(ViewError::Corrupted("range out of bounds"));
+            }
+            let (start, len) = node.children;
+            if (start..start + len).any(|c| self.child(c) >= self.node_count) {
+                return Err(ViewError::Corrupted("child index out of bounds"));
+            }
+        }
+        Ok(())
+    }
+
+    fn lookup(&self, c: u8) -> u128 {
+        read_mask(self.lookup_table, c as usize * self.width, self.width)
+    }
+
+    fn child(&self, i: usize) -> usize {
+        read_u64(self.children, i * 8) as usize
+    }
+
+    fn node(&self, i: usize) -> NodeRecord {
+        let offset = i * node_len(self.width);
+        let fields = offset + 1 + self.width;
+        let field = |n: usize| read_u64(self.nodes, fields + n * 8) as usize;
+        NodeRecord {
+            kind: self.nodes[offset],
+            mask: read_mask(self.nodes, offset + 1, self.width),
+            children: (field(0), field(1)),
+            key: (field(2), field(3)),
+            value: (field(4), field(5)),
+        }
+    }
+
+    fn find_node(&self, input: &[u8]) -> Option<NodeRecord> {
+        let mut node = self.node(0);
+        for &c in input {
+            let c_mask = self.lookup(c);
+            if c_mask & node.mask == 0 {
+                return None;
+            }
+            let child_index = ((c_mask - 1) & node.mask).count_ones() as usize;
+            node = self.node(self.child(node.children.0 + child_index));
+        }
+        Some(node)
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.node_count
+    }
+
+    pub fn get(&self, input: &[u8]) -> Option<&'a [u8]> {
+        match self.find_node(input) {
+            Some(n) if n.kind == KIND_LEAF => Some(&self.values[n.value.0..n.value.0 + n.value.1]),
+            _ => None,
+        }
+    }
+
+    pub fn get_as<V: DecodeValue<'a>>(&self, input: &[u8]) -> Option<V> {
+        self.get(input).and_then(V::decode_value)
+    }
+
+    pub fn contains(&self, input: &[u8]) -> bool {
+        self.get(input).is_some()
+    }
+
+    pub fn has_prefix(&self, input: &[u8]) -> bool {
+        matches!(self.find_node(input), Some(n) if n.kind == KIND_BRANCH && n.children.1 > 0)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &'a [u8]> + '_ {
+        (0..self.node_count)
+            .map(|i| self.node(i))
+            .filter(|n| n.kind == KIND_LEAF)
+            .map(|n| &self.keys[n.key.0..n.key.0 + n.key.1])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{TrieHarderMap, TrieHarderSet};
+
+    use super::*;
+
+    #[test]
+    fn test_view_roundtrip() {
+        let paths: [&[u8]; 4] = [b"/static/js/", b"/web/index", b"/images/", b"/web"];
+        let upstreams = [
+            "127.0.0.1:8080",
+            "127.0.0.100:8088",
+            "127.0.0.10:8089",
+            "localhost",
+        ];
+        let th: TrieHarderMap<'_, u32, &str> =
+            TrieHarderMap::from_strs_and_values(&paths, &upstreams);
+        let mut bytes = vec![];
+        th.write_to(&mut bytes).unwrap();
+        let view = TrieHarderView::from_bytes(&bytes).unwrap();
+        assert_eq!(view.node_count(), th.nodes.len());
+        for (path, upstream) in paths.iter().zip(upstreams) {
+            assert_eq!(view.get_as::<&str>(path), Some(upstream));
+        }
+        assert_eq!(view.get(b"/web/"), None);
+        assert!(view.has_prefix(b"/web/"));
+        assert!(!view.has_prefix(b"/api"));
+        let mut keys: Vec<_> = view.keys().collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            [&b"/images/"[..], b"/static/js/", b"/web", b"/web/index"]
+        );
+    }
+
+    #[test]
+    fn test_view_set() {
+        let words: [&[u8]; 5] = [b"and", b"ant", b"dad", b"do", b"dot"];
+        let th: TrieHarderSet<'_, u8> = TrieHarderSet::from_strs(&words);
+        let bytes = th.to_bytes();
+        let view = TrieHarderView::from_bytes(&bytes).unwrap();
+        assert!(view.contains(b"do"));
+        assert!(view.contains(b"dot"));
+        assert!(!view.contains(b"da"));
+        assert_eq!(view.get_as::<()>(b"dad"), Some(()));
+    }
+
+    #[test]
+    fn test_view_rejects_bad_input() {
+        let words: [&[u8]; 2] = [b"and", b"ant"];
+        let values = [1_u64, 2];
+        let th: TrieHarderMap<'_, u16, u64> = TrieHarderMap::from_strs_and_values(&words, &values);
+        let bytes = th.to_bytes();
+        assert_eq!(
+            TrieHarderView::from_bytes(&bytes[..10]).unwrap_err(),
+            ViewError::TooShort
+        );
+        assert_eq!(
+            TrieHarderView::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err(),
+            ViewError::LengthMismatch
+        );
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() ^= 0xff;
+        assert_eq!(
+            TrieHarderView::from_bytes(&corrupted).unwrap_err(),
+            ViewError::ChecksumMismatch
+        );
+        let mut corrupted = bytes.clone();
+        corrupted[4] = 9;
+        assert_eq!(
+            TrieHarderView::from_bytes(&corrupted).unwrap_err(),
+            ViewError::UnsupportedVersion(9)
+        );
+        let view = TrieHarderView::from_bytes(&bytes).unwrap();
+        assert_eq!(view.get_as::<u64>(b"ant"), Some(2));
+    }
+}
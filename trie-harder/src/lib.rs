@@ -1,5 +1,21 @@
 use std::ops::{AddAssign, BitAnd, BitOrAssign, Index, Shl, Sub};
 
+mod binary;
+mod mapper;
+mod router;
+mod scan;
+mod search;
+mod set;
+mod shared;
+mod stats;
+
+pub use binary::{DecodeValue, EncodeValue, TrieHarderView, ViewError};
+pub use mapper::{AsciiCaseFold, ByteMapper, DashUnderscore, Identity};
+pub use router::{Params, PatternError, TrieHarderRouter};
+pub use scan::{AhoCorasick, StreamScanner};
+pub use shared::{Change, SharedTrieHarderMap, TrieHarderSnapshot};
+pub use stats::TrieStats;
+
 pub type TrieHarderMapU8<'th, V> = TrieHarderMap<'th, u8, V>;
 pub type TrieHarderMapU16<'th, V> = TrieHarderMap<'th, u16, V>;
 pub type TrieHarderMapU32<'th, V> = TrieHarderMap<'th, u32, V>;
@@ -29,6 +45,10 @@ where
         Self::from_strs_and_values(input, &vec![(); input.len()])
     }
 
+    pub fn from_strs_with_mapper(input: &[&'th [u8]], mapper: impl ByteMapper) -> Self {
+        Self::from_strs_and_values_with_mapper(input, &vec![(); input.len()], mapper)
+    }
+
     pub fn contains(&self, key: &[u8]) -> bool {
         self.get(key).is_some()
     }
@@ -37,6 +57,8 @@ where
 #[derive(Debug)]
 pub struct TrieHarderMap<'th, T, V> {
     lookup_table: LookupTable<T>,
+    // what the `ByteMapper` sent each byte to, so a rebuild can reuse it
+    classes: [u8; 256],
     nodes: Vec<TrieNode<'th, T, V>>,
 }
 
@@ -46,6 +68,7 @@ pub struct LookupTable<T>([Option<T>; 256]);
 pub trait UnsignedInt: Copy {
     fn zero() -> Self;
     fn one() -> Self;
+    fn to_u128(self) -> u128;
 }
 
 macro_rules! impl_unsigned_int {
@@ -58,6 +81,9 @@ macro_rules! impl_unsigned_int {
                 fn one() -> Self {
                     1
                 }
+                fn to_u128(self) -> u128 {
+                    self as u128
+                }
             }
         )*
     };
@@ -167,6 +193,13 @@ impl<'th, T: UnsignedInt, V> TrieNode<'th, T, V> {
         }
     }
 
+    pub fn string(&self) -> &'th [u8] {
+        match self {
+            TrieNode::Branch(n) => n.string,
+            TrieNode::Leaf(n) => n.string,
+        }
+    }
+
     pub fn children(&self) -> &[usize] {
         match self {
             TrieNode::Branch(n) => &n.children,
@@ -176,24 +209,22 @@ impl<'th, T: UnsignedInt, V> TrieNode<'th, T, V> {
 
     pub fn add_child(&mut self, child: &TrieNode<'_, T, V>, lookup: &LookupTable<T>)
     where
-        T: BitOrAssign<T>,
+        T: BitOrAssign<T> + BitAnd<T, Output = T> + Sub<T, Output = T> + OneCounter,
     {
         let (inner_string, inner_index) = match child {
             TrieNode::Branch(n) => (n.string, n.index),
             TrieNode::Leaf(n) => (n.string, n.index),
         };
-        match self {
-            TrieNode::Branch(n) => {
-                let c = inner_string[inner_string.len() - 1];
-                n.mask |= lookup[c].expect("no character find in lookup talbe");
-                n.children.push(inner_index);
-            }
-            TrieNode::Leaf(n) => {
-                let c = inner_string[inner_string.len() - 1];
-                n.mask |= lookup[c].expect("no character find in lookup table");
-                n.children.push(inner_index);
-            }
-        }
+        let (mask, children) = match self {
+            TrieNode::Branch(n) => (&mut n.mask, &mut n.children),
+            TrieNode::Leaf(n) => (&mut n.mask, &mut n.children),
+        };
+        let c = inner_string[inner_string.len() - 1];
+        let c_mask = lookup[c].expect("no character find in lookup table");
+        // children must follow mask bit order, which is not always insertion order
+        let position = ((c_mask - T::one()) & *mask).ones_count();
+        *mask |= c_mask;
+        children.insert(position as usize, inner_index);
     }
 }
 
@@ -210,6 +241,17 @@ where
         + Sub<T, Output = T>,
 {
     pub fn from_strs_and_values(input: &[&'th [u8]], values: &[V]) -> Self {
+        Self::from_strs_and_values_with_mapper(input, values, Identity)
+    }
+
+    // Bytes the mapper sends to the same value share one mask in the lookup
+    // table, so `find_node` matches them with no extra work per lookup.
+    pub fn from_strs_and_values_with_mapper(
+        input: &[&'th [u8]],
+        values: &[V],
+        mapper: impl ByteMapper,
+    ) -> Self {
+        let classes = mapper::byte_classes(&mapper);
         let mut i = 0;
         let mut mask_index = T::zero();
         let mut lookup_table: LookupTable<T> = LookupTable([None; 256]);
@@ -224,8 +266,16 @@ where
                 if lookup_table.0[c as usize].is_some() {
                     continue;
                 }
+                assert!(
+                    mask_index.to_u128() < 8 * std::mem::size_of::<T>() as u128,
+                    "more distinct bytes than bits in the mask type"
+                );
                 let mask = T::one() << mask_index;
-                lookup_table.0[c as usize] = Some(mask);
+                for (b, &class) in classes.iter().enumerate() {
+                    if class == classes[c as usize] {
+                        lookup_table.0[b] = Some(mask);
+                    }
+                }
                 mask_index += T::one();
             }
             if is_done {
@@ -238,6 +288,14 @@ where
         let mut last_node_index = 0;
         let mut nodes = vec![root];
         for (&data, value) in input.iter().zip(values) {
+            if data.is_empty() {
+                // the empty key lives on the root, which keeps its children
+                let mut node = TrieNode::new_leaf(data, 0, value.clone());
+                *node.mask_mut() = nodes[0].mask();
+                node.children_mut().extend(nodes[0].children());
+                nodes[0] = node;
+                continue;
+            }
             for (i, &c) in data.iter().enumerate() {
                 let mut node = if i < data.len() - 1 {
                     TrieNode::new_branch(&data[..i + 1], node_index)
@@ -269,6 +327,7 @@ where
         }
         Self {
             lookup_table,
+            classes,
             nodes,
         }
     }
@@ -280,26 +339,39 @@ where
         }
     }
 
+    // true when `input` is not a key itself but some longer key starts with it
     pub fn has_prefix(&self, input: &[u8]) -> bool {
-        matches!(self.find_node(input), Some(TrieNode::Branch(_)))
+        matches!(self.find_node(input), Some(n @ TrieNode::Branch(_)) if !n.children().is_empty())
     }
 
-    fn find_node(&self, input: &[u8]) -> Option<&TrieNode<'_, T, V>>
-    where
-        T: UnsignedInt + BitAnd<T, Output = T> + Eq + Sub<T, Output = T> + OneCounter,
-    {
-        let mut node = &self.nodes[0];
+    fn find_node(&self, input: &[u8]) -> Option<&TrieNode<'th, T, V>> {
+        self.walk(&self.nodes[0], input)
+    }
+
+    fn walk<'s>(
+        &'s self,
+        mut node: &'s TrieNode<'th, T, V>,
+        input: &[u8],
+    ) -> Option<&'s TrieNode<'th, T, V>> {
         for &c in input {
-            let c_mask = self.lookup_table[c]?;
-            if (c_mask & node.mask()) == T::zero() {
-                return None;
-            }
-            let child_index = ((c_mask - T::one()) & node.mask()).ones_count();
-            let next_node_index = node.children()[child_index as usize];
-            node = &self.nodes[next_node_index];
+            node = self.child_node(node, c)?;
         }
         Some(node)
     }
+
+    fn child_node<'s>(
+        &'s self,
+        node: &TrieNode<'th, T, V>,
+        c: u8,
+    ) -> Option<&'s TrieNode<'th, T, V>> {
+        let c_mask = self.lookup_table[c]?;
+        if (c_mask & node.mask()) == T::zero() {
+            return None;
+        }
+        let child_index = ((c_mask - T::one()) & node.mask()).ones_count();
+        let next_node_index = node.children()[child_index as usize];
+        Some(&self.nodes[next_node_index])
+    }
 }
 
 #[cfg(test)]
@@ -361,6 +433,16 @@ mod test {
         assert!(!th.contains(b"da"));
     }
 
+    #[test]
+    fn test_children_follow_mask_order() {
+        // `a` and `b` get their masks at depth 0, before `x`'s children are inserted
+        let words: [&[u8]; 4] = [b"xb", b"xa", b"a", b"b"];
+        let th: TrieHarderSet<'_, u8> = TrieHarderSet::from_strs(&words);
+        for word in words {
+            assert!(th.contains(word));
+        }
+    }
+
     #[test]
     fn test_trie_harder_map() {
         let paths: [&[u8]; 3] = [b"/static/js/", b"/web/index", b"/images/"];
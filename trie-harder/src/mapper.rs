@@ -0,0 +1,97 @@
+pub trait ByteMapper {
+    fn map_byte(&self, b: u8) -> u8;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Identity;
+
+impl ByteMapper for Identity {
+    fn map_byte(&self, b: u8) -> u8 {
+        b
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AsciiCaseFold;
+
+impl ByteMapper for AsciiCaseFold {
+    fn map_byte(&self, b: u8) -> u8 {
+        b.to_ascii_lowercase()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DashUnderscore;
+
+impl ByteMapper for DashUnderscore {
+    fn map_byte(&self, b: u8) -> u8 {
+        if b == b'_' {
+            b'-'
+        } else {
+            b
+        }
+    }
+}
+
+// chain two mappers, e.g. `(AsciiCaseFold, DashUnderscore)`
+impl<A: ByteMapper, B: ByteMapper> ByteMapper for (A, B) {
+    fn map_byte(&self, b: u8) -> u8 {
+        self.1.map_byte(self.0.map_byte(b))
+    }
+}
+
+impl<F: Fn(u8) -> u8> ByteMapper for F {
+    fn map_byte(&self, b: u8) -> u8 {
+        self(b)
+    }
+}
+
+pub(crate) fn byte_classes(mapper: &impl ByteMapper) -> [u8; 256] {
+    let mut classes = [0; 256];
+    for (b, class) in classes.iter_mut().enumerate() {
+        *class = mapper.map_byte(b as u8);
+    }
+    classes
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{TrieHarderMap, TrieHarderSet};
+
+    use super::*;
+
+    #[test]
+    fn test_ascii_case_fold() {
+        let headers: [&[u8]; 3] = [b"Content-Type", b"content-length", b"Host"];
+        let th: TrieHarderMap<'_, u32, usize> =
+            TrieHarderMap::from_strs_and_values_with_mapper(&headers, &[0, 1, 2], AsciiCaseFold);
+        assert_eq!(th.get(b"content-type"), Some(&0));
+        assert_eq!(th.get(b"CONTENT-TYPE"), Some(&0));
+        assert_eq!(th.get(b"Content-Length"), Some(&1));
+        assert_eq!(th.get(b"hOST"), Some(&2));
+        assert!(th.has_prefix(b"CONTENT-"));
+        assert_eq!(th.get(b"content_type"), None);
+        assert_eq!(
+            th.lookup_table[b'C'].unwrap(),
+            th.lookup_table[b'c'].unwrap()
+        );
+    }
+
+    #[test]
+    fn test_dash_underscore() {
+        let words: [&[u8]; 2] = [b"x-forwarded-for", b"x_real_ip"];
+        let th: TrieHarderSet<'_, u32> =
+            TrieHarderSet::from_strs_with_mapper(&words, (AsciiCaseFold, DashUnderscore));
+        assert!(th.contains(b"X_Forwarded_For"));
+        assert!(th.contains(b"x-real-ip"));
+        assert!(!th.contains(b"x.real.ip"));
+    }
+
+    #[test]
+    fn test_identity_keeps_exact_match() {
+        let words: [&[u8]; 1] = [b"Host"];
+        let th: TrieHarderSet<'_, u8> = TrieHarderSet::from_strs(&words);
+        assert!(th.contains(b"Host"));
+        assert!(!th.contains(b"host"));
+    }
+}
@@ -0,0 +1,277 @@
+use std::{
+    fmt::Display,
+    ops::{AddAssign, BitAnd, BitOrAssign, Shl, Sub},
+};
+
+use crate::{OneCounter, TrieHarderMap, TrieNode, UnsignedInt};
+
+const WILDCARD: &[u8] = b"*";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PatternError {
+    MissingLeadingSlash,
+    EmptyParamName,
+    InvalidParam,
+    WildcardNotLast,
+}
+
+impl std::error::Error for PatternError {}
+
+impl Display for PatternError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatternError::MissingLeadingSlash => write!(f, "route must start with '/'"),
+            PatternError::EmptyParamName => write!(f, "empty path parameter name"),
+            PatternError::InvalidParam => write!(f, "path parameter must be a whole segment"),
+            PatternError::WildcardNotLast => write!(f, "wildcard must be the last segment"),
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Params<'th, 'p>(Vec<(&'th [u8], &'p [u8])>);
+
+impl<'th, 'p> Params<'th, 'p> {
+    pub fn get(&self, name: &[u8]) -> Option<&'p [u8]> {
+        self.0.iter().find(|(n, _)| *n == name).map(|(_, v)| *v)
+    }
+
+    pub fn wildcard(&self) -> Option<&'p [u8]> {
+        self.get(WILDCARD)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'th [u8], &'p [u8])> + '_ {
+        self.0.iter().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+// Routes are stored verbatim in a trie, `{name}` and `*` segments included.
+// Matching goes segment by segment and backtracks, trying the literal edge
+// first, then any `{...}` edge, then `*`.
+#[derive(Debug)]
+pub struct TrieHarderRouter<'th, T, V> {
+    map: TrieHarderMap<'th, T, usize>,
+    values: Vec<V>,
+}
+
+fn validate_route(route: &[u8]) -> Result<(), PatternError> {
+    let Some(segments) = route.strip_prefix(b"/") else {
+        return Err(PatternError::MissingLeadingSlash);
+    };
+    let mut segments = segments.split(|&c| c == b'/').peekable();
+    while let Some(seg) = segments.next() {
+        if seg == WILDCARD {
+            if segments.peek().is_some() {
+                return Err(PatternError::WildcardNotLast);
+            }
+        } else if seg.contains(&b'*') {
+            return Err(PatternError::WildcardNotLast);
+        } else if let Some(name) = seg.strip_prefix(b"{").and_then(|s| s.strip_suffix(b"}")) {
+            if name.is_empty() {
+                return Err(PatternError::EmptyParamName);
+            }
+            if name.contains(&b'{') || name.contains(&b'}') {
+                return Err(PatternError::InvalidParam);
+            }
+        } else if seg.contains(&b'{') || seg.contains(&b'}') {
+            return Err(PatternError::InvalidParam);
+        }
+    }
+    Ok(())
+}
+
+impl<'th, T, V> TrieHarderRouter<'th, T, V>
+where
+    V: Clone,
+    T: OneCounter
+        + UnsignedInt
+        + Shl<T, Output = T>
+        + AddAssign
+        + BitAnd<T, Output = T>
+        + Eq
+        + BitOrAssign
+        + Sub<T, Output = T>,
+{
+    pub fn from_routes_and_values(
+        routes: &[&'th [u8]],
+        values: &[V],
+    ) -> Result<Self, PatternError> {
+        for route in routes {
+            validate_route(route)?;
+        }
+        let indices: Vec<_> = (0..values.len()).collect();
+        Ok(Self {
+            map: TrieHarderMap::from_strs_and_values(routes, &indices),
+            values: values.to_vec(),
+        })
+    }
+
+    pub fn at<'p>(&self, path: &'p [u8]) -> Option<(&V, Params<'th, 'p>)> {
+        let rest = path.strip_prefix(b"/")?;
+        let node = self.map.child_node(&self.map.nodes[0], b'/')?;
+        let mut params = vec![];
+        let index = self.match_segment(node, rest, &mut params)?;
+        Some((&self.values[index], Params(params)))
+    }
+
+    // `node` sits right after a '/', `rest` is the path after that '/'
+    fn match_segment<'p>(
+        &self,
+        node: &TrieNode<'th, T, usize>,
+        rest: &'p [u8],
+        params: &mut Vec<(&'th [u8], &'p [u8])>,
+    ) -> Option<usize> {
+        if rest.is_empty() {
+            if let TrieNode::Leaf(n) = node {
+                return Some(n.value);
+            }
+        } else {
+            let end = rest.iter().position(|&c| c == b'/').unwrap_or(rest.len());
+            let (seg, tail) = rest.split_at(end);
+            if !seg.starts_with(b"{") && seg != WILDCARD {
+                if let Some(index) = self
+                    .map
+                    .walk(node, seg)
+                    .and_then(|n| self.match_tail(n, tail, params))
+                {
+                    return Some(index);
+                }
+            }
+            if !seg.is_empty() {
+                if let Some(open) = self.map.child_node(node, b'{') {
+                    let name_start = open.string().len();
+                    if let Some(index) = self.match_param(open, name_start, seg, tail, params) {
+                        return Some(index);
+                    }
+                }
+            }
+        }
+        match self.map.child_node(node, b'*') {
+            Some(TrieNode::Leaf(n)) => {
+                params.push((WILDCARD, rest));
+                Some(n.value)
+            }
+            _ => None,
+        }
+    }
+
+    // walks every `{name}` edge below `node`, there may be several names at one level
+    fn match_param<'p>(
+        &self,
+        node: &TrieNode<'th, T, usize>,
+        name_start: usize,
+        seg: &'p [u8],
+        tail: &'p [u8],
+        params: &mut Vec<(&'th [u8], &'p [u8])>,
+    ) -> Option<usize> {
+        for &child in node.children() {
+            let child = &self.map.nodes[child];
+            let string = child.string();
+            if string.ends_with(b"}") {
+                params.push((&string[name_start..string.len() - 1], seg));
+                if let Some(index) = self.match_tail(child, tail, params) {
+                    return Some(index);
+                }
+                params.pop();
+            } else if let Some(index) = self.match_param(child, name_start, seg, tail, params) {
+                return Some(index);
+            }
+        }
+        None
+    }
+
+    fn match_tail<'p>(
+        &self,
+        node: &TrieNode<'th, T, usize>,
+        tail: &'p [u8],
+        params: &mut Vec<(&'th [u8], &'p [u8])>,
+    ) -> Option<usize> {
+        if tail.is_empty() {
+            return match node {
+                TrieNode::Leaf(n) => Some(n.value),
+                TrieNode::Branch(_) => None,
+            };
+        }
+        let node = self.map.child_node(node, b'/')?;
+        self.match_segment(node, &tail[1..], params)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn router<'th>(routes: &[&'th [u8]]) -> TrieHarderRouter<'th, u32, usize> {
+        let values: Vec<_> = (0..routes.len()).collect();
+        TrieHarderRouter::from_routes_and_values(routes, &values).unwrap()
+    }
+
+    #[test]
+    fn test_route_params() {
+        let th = router(&[b"/api/user/{id}/posts/*", b"/api/user/{id}", b"/"]);
+        let (&v, params) = th.at(b"/api/user/42/posts/2024/05").unwrap();
+        assert_eq!(v, 0);
+        assert_eq!(params.get(b"id"), Some(&b"42"[..]));
+        assert_eq!(params.wildcard(), Some(&b"2024/05"[..]));
+        let (&v, params) = th.at(b"/api/user/alice").unwrap();
+        assert_eq!(v, 1);
+        assert_eq!(
+            params.iter().collect::<Vec<_>>(),
+            [(&b"id"[..], &b"alice"[..])]
+        );
+        assert_eq!(th.at(b"/").unwrap().0, &2);
+        assert!(th.at(b"/api/user/").is_none());
+        assert!(th.at(b"/api/user/42/comments").is_none());
+        assert!(th.at(b"api").is_none());
+    }
+
+    #[test]
+    fn test_route_priority() {
+        let th = router(&[b"/files/*", b"/files/{name}", b"/files/index.html"]);
+        assert_eq!(th.at(b"/files/index.html").unwrap().0, &2);
+        assert_eq!(th.at(b"/files/logo.png").unwrap().0, &1);
+        let (&v, params) = th.at(b"/files/images/logo.png").unwrap();
+        assert_eq!(v, 0);
+        assert_eq!(params.wildcard(), Some(&b"images/logo.png"[..]));
+        assert_eq!(th.at(b"/files/").unwrap().0, &0);
+    }
+
+    #[test]
+    fn test_route_backtracking() {
+        let th = router(&[
+            b"/user/admin/settings",
+            b"/user/{id}/profile",
+            b"/user/{uid}/posts/{pid}",
+        ]);
+        let (&v, params) = th.at(b"/user/admin/profile").unwrap();
+        assert_eq!(v, 1);
+        assert_eq!(params.get(b"id"), Some(&b"admin"[..]));
+        let (&v, params) = th.at(b"/user/7/posts/9").unwrap();
+        assert_eq!(v, 2);
+        assert_eq!(params.get(b"uid"), Some(&b"7"[..]));
+        assert_eq!(params.get(b"pid"), Some(&b"9"[..]));
+        assert!(params.get(b"id").is_none());
+        assert_eq!(th.at(b"/user/admin/settings").unwrap().1.len(), 0);
+    }
+
+    #[test]
+    fn test_invalid_routes() {
+        let values = [(); 1];
+        let check = |route: &'static [u8]| {
+            TrieHarderRouter::<'_, u32, ()>::from_routes_and_values(&[route], &values).unwrap_err()
+        };
+        assert_eq!(check(b"api"), PatternError::MissingLeadingSlash);
+        assert_eq!(check(b"/api/{}"), PatternError::EmptyParamName);
+        assert_eq!(check(b"/api/x{id}"), PatternError::InvalidParam);
+        assert_eq!(check(b"/api/*/x"), PatternError::WildcardNotLast);
+        assert_eq!(check(b"/api/a*"), PatternError::WildcardNotLast);
+    }
+}
@@ -0,0 +1,251 @@
+use std::{
+    collections::VecDeque,
+    ops::{AddAssign, BitAnd, BitOrAssign, Shl, Sub},
+};
+
+use crate::{OneCounter, TrieHarderMap, TrieNode, UnsignedInt};
+
+// Aho–Corasick automaton over the trie nodes: `fail[i]` is the node for the
+// longest proper suffix of node `i` that is also in the trie, `output[i]` is
+// the nearest leaf along that failure chain.
+#[derive(Debug)]
+pub struct AhoCorasick<'m, 'th, T, V> {
+    map: &'m TrieHarderMap<'th, T, V>,
+    fail: Vec<usize>,
+    output: Vec<Option<usize>>,
+}
+
+impl<'th, T, V> TrieHarderMap<'th, T, V>
+where
+    V: Clone,
+    T: OneCounter
+        + UnsignedInt
+        + Shl<T, Output = T>
+        + AddAssign
+        + BitAnd<T, Output = T>
+        + Eq
+        + BitOrAssign
+        + Sub<T, Output = T>,
+{
+    pub fn aho_corasick(&self) -> AhoCorasick<'_, 'th, T, V> {
+        let mut fail = vec![0; self.nodes.len()];
+        let mut output = vec![None; self.nodes.len()];
+        let mut queue: VecDeque<_> = self.nodes[0].children().iter().copied().collect();
+        while let Some(u) = queue.pop_front() {
+            for &v in self.nodes[u].children() {
+                let string = self.nodes[v].string();
+                let c = string[string.len() - 1];
+                let mut f = fail[u];
+                let target = loop {
+                    if let Some(n) = self.child_node(&self.nodes[f], c) {
+                        break n.index();
+                    }
+                    if f == 0 {
+                        break 0;
+                    }
+                    f = fail[f];
+                };
+                fail[v] = target;
+                output[v] = match self.nodes[target] {
+                    TrieNode::Leaf(_) if target != 0 => Some(target),
+                    _ => output[target],
+                };
+                queue.push_back(v);
+            }
+        }
+        AhoCorasick {
+            map: self,
+            fail,
+            output,
+        }
+    }
+}
+
+impl<'m, 'th, T, V> AhoCorasick<'m, 'th, T, V>
+where
+    V: Clone,
+    T: OneCounter
+        + UnsignedInt
+        + Shl<T, Output = T>
+        + AddAssign
+        + BitAnd<T, Output = T>
+        + Eq
+        + BitOrAssign
+        + Sub<T, Output = T>,
+{
+    fn next_state(&self, mut state: usize, c: u8) -> usize {
+        loop {
+            if let Some(n) = self.map.child_node(&self.map.nodes[state], c) {
+                return n.index();
+            }
+            if state == 0 {
+                return 0;
+            }
+            state = self.fail[state];
+        }
+    }
+
+    // every pattern ending at the current state, longest first
+    fn matches_at(&self, state: usize) -> impl Iterator<Item = &'m TrieNode<'th, T, V>> + '_ {
+        let first = match self.map.nodes[state] {
+            TrieNode::Leaf(_) if state != 0 => Some(state),
+            _ => self.output[state],
+        };
+        std::iter::successors(first, |&i| self.output[i]).map(|i| &self.map.nodes[i])
+    }
+
+    fn leaf(node: &'m TrieNode<'th, T, V>, end: usize) -> (usize, usize, &'m V) {
+        match node {
+            TrieNode::Leaf(n) => (end - n.string.len(), end, &n.value),
+            TrieNode::Branch(_) => unreachable!("output links only point at leaves"),
+        }
+    }
+
+    // every occurrence, overlapping ones included, ordered by end position
+    pub fn find_overlapping_iter<'h>(
+        &'h self,
+        haystack: &'h [u8],
+    ) -> impl Iterator<Item = (usize, usize, &'m V)> + use<'h, 'm, 'th, T, V> {
+        let mut state = 0;
+        haystack.iter().enumerate().flat_map(move |(i, &c)| {
+            state = self.next_state(state, c);
+            self.matches_at(state).map(move |n| Self::leaf(n, i + 1))
+        })
+    }
+
+    // non-overlapping matches, at each position the leftmost then longest one
+    pub fn find_iter<'h>(
+        &'h self,
+        haystack: &'h [u8],
+    ) -> impl Iterator<Item = (usize, usize, &'m V)> + use<'h, 'm, 'th, T, V> {
+        let mut pos = 0;
+        std::iter::from_fn(move || {
+            let mut state = 0;
+            let mut best: Option<(usize, usize, &'m V)> = None;
+            for (i, &c) in haystack.iter().enumerate().skip(pos) {
+                state = self.next_state(state, c);
+                for m in self.matches_at(state).map(|n| Self::leaf(n, i + 1)) {
+                    if best.is_none_or(|b| m.0 < b.0 || (m.0 == b.0 && m.1 > b.1)) {
+                        best = Some(m);
+                    }
+                }
+                // no later match can start at or before the best one
+                let depth = self.map.nodes[state].string().len();
+                if best.is_some_and(|b| i + 1 - depth > b.0) {
+                    break;
+                }
+            }
+            let found = best?;
+            pos = found.1.max(found.0 + 1);
+            Some(found)
+        })
+    }
+
+    pub fn stream(&self) -> StreamScanner<'_, 'm, 'th, T, V> {
+        StreamScanner {
+            automaton: self,
+            state: 0,
+            offset: 0,
+        }
+    }
+}
+
+// Scans a haystack fed in chunks with overlapping semantics, positions are
+// absolute offsets from the start of the stream.
+#[derive(Debug)]
+pub struct StreamScanner<'a, 'm, 'th, T, V> {
+    automaton: &'a AhoCorasick<'m, 'th, T, V>,
+    state: usize,
+    offset: usize,
+}
+
+impl<'m, 'th, T, V> StreamScanner<'_, 'm, 'th, T, V>
+where
+    V: Clone,
+    T: OneCounter
+        + UnsignedInt
+        + Shl<T, Output = T>
+        + AddAssign
+        + BitAnd<T, Output = T>
+        + Eq
+        + BitOrAssign
+        + Sub<T, Output = T>,
+{
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<(usize, usize, &'m V)> {
+        let mut result = vec![];
+        for &c in chunk {
+            self.state = self.automaton.next_state(self.state, c);
+            self.offset += 1;
+            result.extend(
+                self.automaton
+                    .matches_at(self.state)
+                    .map(|n| AhoCorasick::leaf(n, self.offset)),
+            );
+        }
+        result
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{AsciiCaseFold, TrieHarderMap};
+
+    fn keywords<'th>(words: &[&'th [u8]]) -> TrieHarderMap<'th, u32, usize> {
+        let values: Vec<_> = (0..words.len()).collect();
+        TrieHarderMap::from_strs_and_values(words, &values)
+    }
+
+    #[test]
+    fn test_find_overlapping() {
+        let th = keywords(&[b"he", b"she", b"his", b"hers"]);
+        let ac = th.aho_corasick();
+        let found: Vec<_> = ac.find_overlapping_iter(b"ushers").collect();
+        assert_eq!(found, [(1, 4, &1), (2, 4, &0), (2, 6, &3)]);
+        let found: Vec<_> = ac.find_overlapping_iter(b"ahishers").collect();
+        assert_eq!(found, [(1, 4, &2), (3, 6, &1), (4, 6, &0), (4, 8, &3)]);
+        assert_eq!(ac.find_overlapping_iter(b"xyz").count(), 0);
+    }
+
+    #[test]
+    fn test_find_leftmost_longest() {
+        let th = keywords(&[b"abcd", b"bc", b"b", b"cde", b"a"]);
+        let ac = th.aho_corasick();
+        let found: Vec<_> = ac.find_iter(b"xabcdex").collect();
+        assert_eq!(found, [(1, 5, &0)]);
+        let found: Vec<_> = ac.find_iter(b"abcbcde").collect();
+        assert_eq!(found, [(0, 1, &4), (1, 3, &1), (3, 5, &1)]);
+        let th = keywords(&[b"content-type", b"content", b"type"]);
+        let ac = th.aho_corasick();
+        let found: Vec<_> = ac.find_iter(b"content-type: type").collect();
+        assert_eq!(found, [(0, 12, &0), (14, 18, &2)]);
+    }
+
+    #[test]
+    fn test_stream_chunks() {
+        let th = keywords(&[b"he", b"she", b"his", b"hers"]);
+        let ac = th.aho_corasick();
+        let haystack = b"ahishers she said";
+        let expected: Vec<_> = ac.find_overlapping_iter(haystack).collect();
+        for split in 0..=haystack.len() {
+            let mut stream = ac.stream();
+            let mut found = stream.feed(&haystack[..split]);
+            found.extend(stream.feed(&haystack[split..]));
+            assert_eq!(found, expected);
+            assert_eq!(stream.offset(), haystack.len());
+        }
+    }
+
+    #[test]
+    fn test_case_insensitive_scan() {
+        let words: [&[u8]; 2] = [b"select", b"drop"];
+        let th: TrieHarderMap<'_, u32, usize> =
+            TrieHarderMap::from_strs_and_values_with_mapper(&words, &[0, 1], AsciiCaseFold);
+        let ac = th.aho_corasick();
+        let found: Vec<_> = ac.find_iter(b"1; DROP table; Select *").collect();
+        assert_eq!(found, [(3, 7, &1), (15, 21, &0)]);
+    }
+}
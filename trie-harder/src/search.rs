@@ -0,0 +1,170 @@
+use std::ops::{AddAssign, BitAnd, BitOrAssign, Shl, Sub};
+
+use crate::{OneCounter, TrieHarderMap, TrieNode, UnsignedInt};
+
+impl<'th, T, V> TrieHarderMap<'th, T, V>
+where
+    V: Clone,
+    T: OneCounter
+        + UnsignedInt
+        + Shl<T, Output = T>
+        + AddAssign
+        + BitAnd<T, Output = T>
+        + Eq
+        + BitOrAssign
+        + Sub<T, Output = T>,
+{
+    // Keys within `max_edits` Levenshtein distance of `query`, closest first.
+    // One DP row is kept per trie level, so shared prefixes are computed once
+    // and a subtree is skipped as soon as every cell in its row is too large.
+    // Bytes compare by their mask, so a `ByteMapper` applies as in `get`.
+    pub fn fuzzy_search(&self, query: &[u8], max_edits: usize) -> Vec<(&'th [u8], usize)> {
+        let query: Vec<_> = query.iter().map(|&q| self.lookup_table[q]).collect();
+        let row: Vec<_> = (0..=query.len()).collect();
+        let mut result = vec![];
+        let root = &self.nodes[0];
+        if let TrieNode::Leaf(_) = root {
+            if query.len() <= max_edits {
+                result.push((root.string(), query.len()));
+            }
+        }
+        self.fuzzy_walk(root, &query, max_edits, &row, &mut result);
+        result.sort_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(b.0)));
+        result
+    }
+
+    fn fuzzy_walk(
+        &self,
+        node: &TrieNode<'th, T, V>,
+        query: &[Option<T>],
+        max_edits: usize,
+        prev: &[usize],
+        result: &mut Vec<(&'th [u8], usize)>,
+    ) {
+        for &child in node.children() {
+            let child = &self.nodes[child];
+            let string = child.string();
+            let c = self.lookup_table[string[string.len() - 1]];
+            let mut row = Vec::with_capacity(prev.len());
+            row.push(prev[0] + 1);
+            for (j, &q) in query.iter().enumerate() {
+                let replace = prev[j] + usize::from(q != c);
+                row.push(replace.min(prev[j + 1] + 1).min(row[j] + 1));
+            }
+            let distance = row[query.len()];
+            if matches!(child, TrieNode::Leaf(_)) && distance <= max_edits {
+                result.push((string, distance));
+            }
+            if row.iter().any(|&d| d <= max_edits) {
+                self.fuzzy_walk(child, query, max_edits, &row, result);
+            }
+        }
+    }
+
+    // Up to `limit` keys starting with `prefix`, in byte order.
+    pub fn complete(&self, prefix: &[u8], limit: usize) -> Vec<&'th [u8]> {
+        let mut result = vec![];
+        if let Some(node) = self.find_node(prefix) {
+            self.complete_walk(node, limit, &mut result);
+        }
+        result
+    }
+
+    fn complete_walk(&self, node: &TrieNode<'th, T, V>, limit: usize, result: &mut Vec<&'th [u8]>) {
+        if result.len() >= limit {
+            return;
+        }
+        if let TrieNode::Leaf(_) = node {
+            result.push(node.string());
+        }
+        // children are kept in mask order, which is not byte order
+        let mut children: Vec<_> = node.children().iter().map(|&i| &self.nodes[i]).collect();
+        children.sort_by_key(|n| n.string().last().copied());
+        for child in children {
+            self.complete_walk(child, limit, result);
+        }
+    }
+
+    // Up to `limit` keys starting with `prefix`, heaviest first, ties in byte order.
+    pub fn complete_by_weight<W: Ord>(
+        &self,
+        prefix: &[u8],
+        limit: usize,
+        weight: impl Fn(&V) -> W,
+    ) -> Vec<&'th [u8]> {
+        let Some(node) = self.find_node(prefix) else {
+            return vec![];
+        };
+        let mut leaves = vec![];
+        let mut stack = vec![node];
+        while let Some(node) = stack.pop() {
+            if let TrieNode::Leaf(n) = node {
+                leaves.push((weight(&n.value), n.string));
+            }
+            stack.extend(node.children().iter().map(|&i| &self.nodes[i]));
+        }
+        leaves.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(b.1)));
+        leaves.into_iter().take(limit).map(|(_, key)| key).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{AsciiCaseFold, TrieHarderMap, TrieHarderSet};
+
+    #[test]
+    fn test_fuzzy_search() {
+        let words: [&[u8]; 7] = [
+            b"hello", b"help", b"hell", b"yellow", b"world", b"word", b"h",
+        ];
+        let th: TrieHarderSet<'_, u32> = TrieHarderSet::from_strs(&words);
+        assert_eq!(th.fuzzy_search(b"hell", 0), [(&b"hell"[..], 0)]);
+        assert_eq!(
+            th.fuzzy_search(b"helo", 1),
+            [(&b"hell"[..], 1), (&b"hello"[..], 1), (&b"help"[..], 1)]
+        );
+        assert_eq!(th.fuzzy_search(b"wrld", 1), [(&b"world"[..], 1)]);
+        assert_eq!(
+            th.fuzzy_search(b"wrld", 2),
+            [(&b"world"[..], 1), (&b"word"[..], 2)]
+        );
+        assert_eq!(th.fuzzy_search(b"", 1), [(&b"h"[..], 1)]);
+        assert!(th.fuzzy_search(b"xyz", 1).is_empty());
+    }
+
+    #[test]
+    fn test_fuzzy_search_case_folded() {
+        let headers: [&[u8]; 2] = [b"Host", b"Accept"];
+        let th: TrieHarderSet<'_, u32> =
+            TrieHarderSet::from_strs_with_mapper(&headers, AsciiCaseFold);
+        assert_eq!(th.get(b"host"), Some(&()));
+        assert_eq!(th.fuzzy_search(b"host", 0), [(&b"Host"[..], 0)]);
+        assert_eq!(th.fuzzy_search(b"ACCEPTS", 1), [(&b"Accept"[..], 1)]);
+        assert!(th.fuzzy_search(b"hos\xff", 0).is_empty());
+    }
+
+    #[test]
+    fn test_complete() {
+        let words: [&[u8]; 6] = [b"dot", b"do", b"and", b"ant", b"dad", b"door"];
+        let th: TrieHarderSet<'_, u8> = TrieHarderSet::from_strs(&words);
+        assert_eq!(th.complete(b"d", 10), [&b"dad"[..], b"do", b"door", b"dot"]);
+        assert_eq!(th.complete(b"do", 2), [&b"do"[..], b"door"]);
+        assert_eq!(th.complete(b"", 3), [&b"and"[..], b"ant", b"dad"]);
+        assert!(th.complete(b"x", 3).is_empty());
+    }
+
+    #[test]
+    fn test_complete_by_weight() {
+        let words: [&[u8]; 4] = [b"rust", b"ruby", b"rune", b"go"];
+        let th: TrieHarderMap<'_, u16, u32> =
+            TrieHarderMap::from_strs_and_values(&words, &[50, 10, 50, 99]);
+        assert_eq!(
+            th.complete_by_weight(b"ru", 2, |&w| w),
+            [&b"rune"[..], b"rust"]
+        );
+        assert_eq!(
+            th.complete_by_weight(b"", 4, |&w| w),
+            [&b"go"[..], b"rune", b"rust", b"ruby"]
+        );
+    }
+}
@@ -0,0 +1,542 @@
+use std::ops::{AddAssign, BitAnd, BitOrAssign, Shl, Sub};
+
+use crate::{LookupTable, OneCounter, TrieHarderMap, TrieHarderSet, TrieNode, UnsignedInt};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SetOp {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl SetOp {
+    fn keep(self, in_lhs: bool, in_rhs: bool) -> bool {
+        match self {
+            SetOp::Union => in_lhs || in_rhs,
+            SetOp::Intersection => in_lhs && in_rhs,
+            SetOp::Difference => in_lhs && !in_rhs,
+        }
+    }
+
+    fn descend(self, in_lhs: bool, in_rhs: bool) -> bool {
+        match self {
+            SetOp::Union => in_lhs || in_rhs,
+            SetOp::Intersection => in_lhs && in_rhs,
+            SetOp::Difference => in_lhs,
+        }
+    }
+}
+
+struct Combine<'a, 'th, T, V, F> {
+    lhs: &'a TrieHarderMap<'th, T, V>,
+    rhs: &'a TrieHarderMap<'th, T, V>,
+    op: SetOp,
+    resolve: F,
+    lookup_table: LookupTable<T>,
+    nodes: Vec<TrieNode<'th, T, V>>,
+}
+
+impl<'th, T, V, F> Combine<'_, 'th, T, V, F>
+where
+    V: Clone,
+    F: FnMut(&[u8], &V, &V) -> V,
+    T: OneCounter
+        + UnsignedInt
+        + Shl<T, Output = T>
+        + AddAssign
+        + BitAnd<T, Output = T>
+        + Eq
+        + BitOrAssign
+        + Sub<T, Output = T>,
+{
+    // Masks are handed out in byte order, so walking children in byte order
+    // appends them in mask order. Bytes that share a mask in an input table
+    // (see `ByteMapper`) keep sharing one in the result.
+    fn build_lookup_table(&mut self) {
+        assert!(
+            same_byte_classes(&self.lhs.lookup_table, &self.rhs.lookup_table),
+            "tries built with different byte mappers"
+        );
+        let mut used = [false; 256];
+        for node in self.lhs.nodes.iter().chain(&self.rhs.nodes) {
+            if let Some(&c) = node.string().last() {
+                used[c as usize] = true;
+            }
+        }
+        let mut mask_index = T::zero();
+        for c in 0..=u8::MAX {
+            if !used[c as usize] || self.lookup_table[c].is_some() {
+                continue;
+            }
+            assert!(
+                mask_index.to_u128() < 8 * std::mem::size_of::<T>() as u128,
+                "more distinct bytes than bits in the mask type"
+            );
+            let mask = T::one() << mask_index;
+            mask_index += T::one();
+            for table in [&self.lhs.lookup_table, &self.rhs.lookup_table] {
+                let Some(class) = table[c] else {
+                    continue;
+                };
+                for (b, m) in table.0.iter().enumerate() {
+                    if *m == Some(class) && self.lookup_table.0[b].is_none() {
+                        self.lookup_table.0[b] = Some(mask);
+                    }
+                }
+            }
+        }
+    }
+
+    fn walk(
+        &mut self,
+        lhs: Option<&TrieNode<'th, T, V>>,
+        rhs: Option<&TrieNode<'th, T, V>>,
+    ) -> Option<usize> {
+        let string = lhs.or(rhs)?.string();
+        let index = self.nodes.len();
+        let (lhs_value, rhs_value) = (leaf_value(lhs), leaf_value(rhs));
+        let value = match (lhs_value, rhs_value) {
+            _ if !self.op.keep(lhs_value.is_some(), rhs_value.is_some()) => None,
+            (Some(l), Some(r)) if self.op == SetOp::Union => Some((self.resolve)(string, l, r)),
+            (l, r) => l.or(r).cloned(),
+        };
+        self.nodes.push(match value {
+            Some(value) => TrieNode::new_leaf(string, index, value),
+            None => TrieNode::new_branch(string, index),
+        });
+
+        let lhs_children = lhs
+            .into_iter()
+            .flat_map(|n| n.children().iter().map(|&i| &self.lhs.nodes[i]));
+        let rhs_children = rhs
+            .into_iter()
+            .flat_map(|n| n.children().iter().map(|&i| &self.rhs.nodes[i]));
+        let mut children: Vec<(T, u8)> = lhs_children
+            .chain(rhs_children)
+            .map(|n| {
+                let c = *n.string().last().unwrap();
+                (self.lookup_table[c].unwrap(), c)
+            })
+            .collect();
+        children.sort_by_key(|&(mask, _)| mask.to_u128());
+        children.dedup_by_key(|&mut (mask, _)| mask.to_u128());
+        for (_, c) in children {
+            let lhs_child = lhs.and_then(|n| self.lhs.child_node(n, c));
+            let rhs_child = rhs.and_then(|n| self.rhs.child_node(n, c));
+            if !self.op.descend(lhs_child.is_some(), rhs_child.is_some()) {
+                continue;
+            }
+            if let Some(child) = self.walk(lhs_child, rhs_child) {
+                let (head, tail) = self.nodes.split_at_mut(child);
+                head[index].add_child(&tail[0], &self.lookup_table);
+            }
+        }
+
+        if index > 0 && self.nodes[index].children().is_empty() {
+            if let TrieNode::Branch(_) = self.nodes[index] {
+                self.nodes.truncate(index);
+                return None;
+            }
+        }
+        Some(index)
+    }
+}
+
+// The maps do not keep their `ByteMapper`, only the classes it produced. Two
+// tables are compatible when every byte both of them know has the same
+// class mates in each, e.g. a case-folded `H` is never combined with an
+// exact one. Tables over disjoint bytes are always compatible.
+fn same_byte_classes<T: PartialEq + Copy>(lhs: &LookupTable<T>, rhs: &LookupTable<T>) -> bool {
+    (0..256).all(|b| {
+        let (Some(l), Some(r)) = (lhs.0[b], rhs.0[b]) else {
+            return true;
+        };
+        (0..256).all(|other| (lhs.0[other] == Some(l)) == (rhs.0[other] == Some(r)))
+    })
+}
+
+// Classes for a result of two different mappers: bytes sharing a mask stay
+// together, bytes the table does not know only match themselves.
+fn table_classes<T: PartialEq + Copy>(table: &LookupTable<T>) -> [u8; 256] {
+    let mut classes = [0; 256];
+    for (b, class) in classes.iter_mut().enumerate() {
+        *class = match table.0[b] {
+            Some(mask) => table.0.iter().position(|m| *m == Some(mask)).unwrap() as u8,
+            None => b as u8,
+        };
+    }
+    classes
+}
+
+fn leaf_value<'a, T, V>(node: Option<&'a TrieNode<'_, T, V>>) -> Option<&'a V> {
+    match node {
+        Some(TrieNode::Leaf(n)) => Some(&n.value),
+        _ => None,
+    }
+}
+
+impl<'th, T, V> TrieHarderMap<'th, T, V>
+where
+    V: Clone,
+    T: OneCounter
+        + UnsignedInt
+        + Shl<T, Output = T>
+        + AddAssign
+        + BitAnd<T, Output = T>
+        + Eq
+        + BitOrAssign
+        + Sub<T, Output = T>,
+{
+    fn combine(&self, other: &Self, op: SetOp, resolve: impl FnMut(&[u8], &V, &V) -> V) -> Self {
+        let mut combine = Combine {
+            lhs: self,
+            rhs: other,
+            op,
+            resolve,
+            lookup_table: LookupTable([None; 256]),
+            nodes: vec![],
+        };
+        combine.build_lookup_table();
+        combine.walk(Some(&self.nodes[0]), Some(&other.nodes[0]));
+        let classes = if self.classes == other.classes {
+            self.classes
+        } else {
+            table_classes(&combine.lookup_table)
+        };
+        Self {
+            lookup_table: combine.lookup_table,
+            classes,
+            nodes: combine.nodes,
+        }
+    }
+
+    // Keys of both maps, `resolve(key, ours, theirs)` picks the value on
+    // conflicts. Like all set operations this panics when the result needs
+    // more masks than `T` has bits, or when the maps were built with byte
+    // mappers that disagree on a byte they share.
+    pub fn merge(&self, other: &Self, resolve: impl FnMut(&[u8], &V, &V) -> V) -> Self {
+        self.combine(other, SetOp::Union, resolve)
+    }
+
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.subset_walk(&self.nodes[0], other, &other.nodes[0])
+    }
+
+    fn subset_walk(
+        &self,
+        node: &TrieNode<'th, T, V>,
+        other: &Self,
+        other_node: &TrieNode<'th, T, V>,
+    ) -> bool {
+        if matches!(node, TrieNode::Leaf(_)) && matches!(other_node, TrieNode::Branch(_)) {
+            return false;
+        }
+        node.children().iter().all(|&i| {
+            let child = &self.nodes[i];
+            let c = *child.string().last().unwrap();
+            other
+                .child_node(other_node, c)
+                .is_some_and(|other_child| self.subset_walk(child, other, other_child))
+        })
+    }
+
+    // key/value pairs in byte order of the keys
+    pub fn iter(&self) -> impl Iterator<Item = (&'th [u8], &V)> {
+        let mut leaves: Vec<_> = self
+            .nodes
+            .iter()
+            .filter_map(|n| match n {
+                TrieNode::Leaf(n) => Some((n.string, &n.value)),
+                TrieNode::Branch(_) => None,
+            })
+            .collect();
+        leaves.sort_by_key(|&(k, _)| k);
+        leaves.into_iter()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &'th [u8]> + '_ {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes
+            .iter()
+            .filter(|n| matches!(n, TrieNode::Leaf(_)))
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<'th, T> TrieHarderSet<'th, T>
+where
+    T: OneCounter
+        + UnsignedInt
+        + Shl<T, Output = T>
+        + AddAssign
+        + BitAnd<T, Output = T>
+        + Eq
+        + BitOrAssign
+        + Sub<T, Output = T>,
+{
+    pub fn union(&self, other: &Self) -> Self {
+        self.combine(other, SetOp::Union, |_, _, _| ())
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        self.combine(other, SetOp::Intersection, |_, _, _| ())
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        self.combine(other, SetOp::Difference, |_, _, _| ())
+    }
+}
+
+impl<'th, T, V> FromIterator<(&'th [u8], V)> for TrieHarderMap<'th, T, V>
+where
+    V: Clone,
+    T: OneCounter
+        + UnsignedInt
+        + Shl<T, Output = T>
+        + AddAssign
+        + BitAnd<T, Output = T>
+        + Eq
+        + BitOrAssign
+        + Sub<T, Output = T>,
+{
+    fn from_iter<I: IntoIterator<Item = (&'th [u8], V)>>(iter: I) -> Self {
+        let (keys, values): (Vec<_>, Vec<_>) = iter.into_iter().unzip();
+        Self::from_strs_and_values(&keys, &values)
+    }
+}
+
+impl<'th, T> FromIterator<&'th [u8]> for TrieHarderSet<'th, T>
+where
+    T: OneCounter
+        + UnsignedInt
+        + Shl<T, Output = T>
+        + AddAssign
+        + BitAnd<T, Output = T>
+        + Eq
+        + BitOrAssign
+        + Sub<T, Output = T>,
+{
+    fn from_iter<I: IntoIterator<Item = &'th [u8]>>(iter: I) -> Self {
+        let keys: Vec<_> = iter.into_iter().collect();
+        Self::from_strs(&keys)
+    }
+}
+
+// the trie is immutable once built, extending rebuilds it with the new keys
+// and the byte classes of the mapper it was built with
+impl<'th, T> Extend<&'th [u8]> for TrieHarderSet<'th, T>
+where
+    T: OneCounter
+        + UnsignedInt
+        + Shl<T, Output = T>
+        + AddAssign
+        + BitAnd<T, Output = T>
+        + Eq
+        + BitOrAssign
+        + Sub<T, Output = T>,
+{
+    fn extend<I: IntoIterator<Item = &'th [u8]>>(&mut self, iter: I) {
+        let keys: Vec<_> = self.keys().chain(iter).collect();
+        let classes = self.classes;
+        *self = Self::from_strs_with_mapper(&keys, move |b: u8| classes[b as usize]);
+    }
+}
+
+impl<'th, T> IntoIterator for TrieHarderSet<'th, T>
+where
+    T: OneCounter
+        + UnsignedInt
+        + Shl<T, Output = T>
+        + AddAssign
+        + BitAnd<T, Output = T>
+        + Eq
+        + BitOrAssign
+        + Sub<T, Output = T>,
+{
+    type Item = &'th [u8];
+    type IntoIter = std::vec::IntoIter<&'th [u8]>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.keys().collect::<Vec<_>>().into_iter()
+    }
+}
+
+impl<'th, T> IntoIterator for &TrieHarderSet<'th, T>
+where
+    T: OneCounter
+        + UnsignedInt
+        + Shl<T, Output = T>
+        + AddAssign
+        + BitAnd<T, Output = T>
+        + Eq
+        + BitOrAssign
+        + Sub<T, Output = T>,
+{
+    type Item = &'th [u8];
+    type IntoIter = std::vec::IntoIter<&'th [u8]>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.keys().collect::<Vec<_>>().into_iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{AsciiCaseFold, TrieHarderMap, TrieHarderSet};
+
+    fn set<'th>(words: &[&'th [u8]]) -> TrieHarderSet<'th, u32> {
+        TrieHarderSet::from_strs(words)
+    }
+
+    #[test]
+    fn test_set_algebra() {
+        let lhs = set(&[b"and", b"ant", b"do", b"dot", b"zoo"]);
+        let rhs = set(&[b"an", b"ant", b"dot", b"dog", b"z"]);
+        let union = lhs.union(&rhs);
+        assert_eq!(
+            union.keys().collect::<Vec<_>>(),
+            [
+                &b"an"[..],
+                b"and",
+                b"ant",
+                b"do",
+                b"dog",
+                b"dot",
+                b"z",
+                b"zoo"
+            ]
+        );
+        assert!(union.has_prefix(b"zo"));
+        let intersection = lhs.intersection(&rhs);
+        assert_eq!(
+            intersection.keys().collect::<Vec<_>>(),
+            [&b"ant"[..], b"dot"]
+        );
+        assert!(!intersection.has_prefix(b"z"));
+        let difference = lhs.difference(&rhs);
+        assert_eq!(
+            difference.keys().collect::<Vec<_>>(),
+            [&b"and"[..], b"do", b"zoo"]
+        );
+        assert!(!difference.contains(b"dot"));
+        assert!(difference.has_prefix(b"zo"));
+        assert!(rhs.difference(&rhs).is_empty());
+    }
+
+    #[test]
+    fn test_is_subset() {
+        let lhs = set(&[b"ant", b"do"]);
+        let rhs = set(&[b"and", b"ant", b"do", b"dot"]);
+        assert!(lhs.is_subset(&rhs));
+        assert!(!rhs.is_subset(&lhs));
+        assert!(!set(&[b"d"]).is_subset(&rhs));
+        assert!(lhs.intersection(&rhs).is_subset(&lhs));
+    }
+
+    #[test]
+    fn test_merge_maps() {
+        let lhs_keys: [&[u8]; 2] = [b"/api", b"/web"];
+        let rhs_keys: [&[u8]; 2] = [b"/api", b"/images"];
+        let lhs: TrieHarderMap<'_, u32, u32> =
+            TrieHarderMap::from_strs_and_values(&lhs_keys, &[1, 2]);
+        let rhs = TrieHarderMap::from_strs_and_values(&rhs_keys, &[10, 20]);
+        let merged = lhs.merge(&rhs, |key, l, r| {
+            assert_eq!(key, b"/api");
+            l + r
+        });
+        assert_eq!(merged.get(b"/api"), Some(&11));
+        assert_eq!(merged.get(b"/web"), Some(&2));
+        assert_eq!(merged.get(b"/images"), Some(&20));
+        assert_eq!(merged.len(), 3);
+    }
+
+    #[test]
+    fn test_union_keeps_byte_classes() {
+        let lhs_keys: [&[u8]; 1] = [b"Host"];
+        let rhs_keys: [&[u8]; 1] = [b"accept"];
+        let lhs: TrieHarderSet<'_, u32> =
+            TrieHarderSet::from_strs_with_mapper(&lhs_keys, AsciiCaseFold);
+        let rhs = TrieHarderSet::from_strs_with_mapper(&rhs_keys, AsciiCaseFold);
+        let union = lhs.union(&rhs);
+        assert!(union.contains(b"host"));
+        assert!(union.contains(b"ACCEPT"));
+    }
+
+    // 16 distinct bytes, each input alone fits the 8 bits of a `u8` mask
+    const LHS_BYTES: [&[u8]; 1] = [b"abcdefgh"];
+    const RHS_BYTES: [&[u8]; 1] = [b"ijklmnop"];
+
+    #[test]
+    #[should_panic(expected = "more distinct bytes than bits in the mask type")]
+    fn test_union_overflowing_mask() {
+        let lhs: TrieHarderSet<'_, u8> = TrieHarderSet::from_strs(&LHS_BYTES);
+        let rhs = TrieHarderSet::from_strs(&RHS_BYTES);
+        lhs.union(&rhs);
+    }
+
+    #[test]
+    #[should_panic(expected = "more distinct bytes than bits in the mask type")]
+    fn test_merge_overflowing_mask() {
+        let lhs: TrieHarderMap<'_, u8, u8> = TrieHarderMap::from_strs_and_values(&LHS_BYTES, &[1]);
+        let rhs = TrieHarderMap::from_strs_and_values(&RHS_BYTES, &[2]);
+        lhs.merge(&rhs, |_, l, _| *l);
+    }
+
+    #[test]
+    #[should_panic(expected = "tries built with different byte mappers")]
+    fn test_mismatched_mappers() {
+        let keys: [&[u8]; 1] = [b"Host"];
+        let folded: TrieHarderSet<'_, u32> =
+            TrieHarderSet::from_strs_with_mapper(&keys, AsciiCaseFold);
+        let exact = TrieHarderSet::from_strs(&keys);
+        folded.difference(&exact);
+    }
+
+    #[test]
+    fn test_disjoint_mappers() {
+        let folded: TrieHarderSet<'_, u32> =
+            TrieHarderSet::from_strs_with_mapper(&[b"Host"], AsciiCaseFold);
+        let exact = TrieHarderSet::from_strs(&[b"xyz"]);
+        let union = folded.union(&exact);
+        assert!(union.contains(b"HOST"));
+        assert!(union.contains(b"xyz"));
+        assert!(!union.contains(b"XYZ"));
+    }
+
+    #[test]
+    fn test_extend_keeps_mapper() {
+        let mut th: TrieHarderSet<'_, u32> =
+            TrieHarderSet::from_strs_with_mapper(&[b"Host"], AsciiCaseFold);
+        th.extend([&b"Accept"[..]]);
+        assert!(th.contains(b"HOST"));
+        assert!(th.contains(b"accept"));
+        assert!(th.contains(b"ACCEPT"));
+        let other = TrieHarderSet::from_strs_with_mapper(&[b"vary"], AsciiCaseFold);
+        assert!(th.union(&other).contains(b"Vary"));
+    }
+
+    #[test]
+    fn test_collection_traits() {
+        let words: [&[u8]; 3] = [b"do", b"and", b"dot"];
+        let mut th: TrieHarderSet<'_, u16> = words.into_iter().collect();
+        assert_eq!(th.len(), 3);
+        th.extend([&b"ant"[..], b"d"]);
+        assert!(th.contains(b"ant"));
+        assert!(th.contains(b"d"));
+        assert!(th.contains(b"dot"));
+        let keys: Vec<_> = (&th).into_iter().collect();
+        assert_eq!(keys, [&b"and"[..], b"ant", b"d", b"do", b"dot"]);
+        assert_eq!(th.into_iter().count(), 5);
+
+        let th: TrieHarderMap<'_, u16, usize> = words.into_iter().zip(0..).collect();
+        assert_eq!(th.get(b"dot"), Some(&2));
+        assert_eq!(th.iter().next(), Some((&b"and"[..], &1)));
+    }
+}
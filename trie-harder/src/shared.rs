@@ -0,0 +1,292 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    ops::{AddAssign, BitAnd, BitOrAssign, Shl, Sub},
+    sync::{Arc, Mutex},
+};
+
+use arc_swap::ArcSwap;
+use ouroboros::self_referencing;
+
+use crate::{OneCounter, TrieHarderMap, UnsignedInt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change<V> {
+    Insert(Vec<u8>, V),
+    Remove(Vec<u8>),
+}
+
+// The trie borrows the keys it was built from, so the two live together
+// here. The trie maps each key to its position, the values are only
+// stored in `values`.
+#[self_referencing]
+struct KeyIndex<T> {
+    keys: Vec<Box<[u8]>>,
+    #[borrows(keys)]
+    #[covariant]
+    map: TrieHarderMap<'this, T, usize>,
+}
+
+// An immutable, self-contained trie, keys and values in key order
+pub struct TrieHarderSnapshot<T, V> {
+    index: KeyIndex<T>,
+    values: Vec<V>,
+    version: u64,
+}
+
+impl<T, V> TrieHarderSnapshot<T, V>
+where
+    T: OneCounter
+        + UnsignedInt
+        + Shl<T, Output = T>
+        + AddAssign
+        + BitAnd<T, Output = T>
+        + Eq
+        + BitOrAssign
+        + Sub<T, Output = T>,
+{
+    fn build(entries: BTreeMap<Box<[u8]>, V>, version: u64) -> Self {
+        let (keys, values): (Vec<_>, Vec<_>) = entries.into_iter().unzip();
+        let index = KeyIndex::new(keys, |keys| {
+            let keys: Vec<&[u8]> = keys.iter().map(AsRef::as_ref).collect();
+            let positions: Vec<usize> = (0..keys.len()).collect();
+            TrieHarderMap::from_strs_and_values(&keys, &positions)
+        });
+        Self {
+            index,
+            values,
+            version,
+        }
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&V> {
+        let i = *self.index.borrow_map().get(key)?;
+        Some(&self.values[i])
+    }
+
+    pub fn has_prefix(&self, key: &[u8]) -> bool {
+        self.index.borrow_map().has_prefix(key)
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], &V)> {
+        self.index
+            .borrow_keys()
+            .iter()
+            .map(AsRef::as_ref)
+            .zip(&self.values)
+    }
+}
+
+impl<T, V: Debug> Debug for TrieHarderSnapshot<T, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let keys = self.index.borrow_keys().iter().map(AsRef::as_ref);
+        let entries: Vec<(&[u8], &V)> = keys.zip(&self.values).collect();
+        f.debug_struct("TrieHarderSnapshot")
+            .field("entries", &entries)
+            .field("version", &self.version)
+            .finish()
+    }
+}
+
+// Readers load the current snapshot without locking, writers rebuild a new
+// trie off to the side and publish it with a single atomic swap. A snapshot
+// is freed once the last reader holding it lets go.
+#[derive(Debug)]
+pub struct SharedTrieHarderMap<T, V> {
+    current: ArcSwap<TrieHarderSnapshot<T, V>>,
+    writer: Mutex<()>,
+}
+
+impl<T, V> SharedTrieHarderMap<T, V>
+where
+    V: Clone,
+    T: OneCounter
+        + UnsignedInt
+        + Shl<T, Output = T>
+        + AddAssign
+        + BitAnd<T, Output = T>
+        + Eq
+        + BitOrAssign
+        + Sub<T, Output = T>,
+{
+    pub fn new(input: &[&[u8]], values: &[V]) -> Self {
+        let entries = input
+            .iter()
+            .zip(values)
+            .map(|(&k, v)| (k.into(), v.clone()))
+            .collect();
+        Self {
+            current: ArcSwap::from_pointee(TrieHarderSnapshot::build(entries, 0)),
+            writer: Mutex::new(()),
+        }
+    }
+
+    pub fn snapshot(&self) -> Arc<TrieHarderSnapshot<T, V>> {
+        self.current.load_full()
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<V> {
+        self.current.load().get(key).cloned()
+    }
+
+    pub fn version(&self) -> u64 {
+        self.current.load().version
+    }
+
+    // applies the batch on top of the latest snapshot and returns the new version
+    pub fn update(&self, changes: impl IntoIterator<Item = Change<V>>) -> u64 {
+        let _guard = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let current = self.current.load();
+        let mut entries: BTreeMap<Box<[u8]>, V> =
+            current.iter().map(|(k, v)| (k.into(), v.clone())).collect();
+        for change in changes {
+            match change {
+                Change::Insert(k, v) => {
+                    entries.insert(k.into(), v);
+                }
+                Change::Remove(k) => {
+                    entries.remove(k.as_slice());
+                }
+            }
+        }
+        let version = current.version + 1;
+        self.current
+            .store(Arc::new(TrieHarderSnapshot::build(entries, version)));
+        version
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        thread,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_update_and_snapshot() {
+        let keys: [&[u8]; 2] = [b"/api", b"/static"];
+        let shared: SharedTrieHarderMap<u32, &str> =
+            SharedTrieHarderMap::new(&keys, &["10.0.0.1", "10.0.0.2"]);
+        let old = shared.snapshot();
+        let version = shared.update([
+            Change::Insert(b"/api".to_vec(), "10.0.0.3"),
+            Change::Insert(b"/web".to_vec(), "10.0.0.4"),
+            Change::Remove(b"/static".to_vec()),
+        ]);
+        assert_eq!(version, 1);
+        assert_eq!(shared.get(b"/api"), Some("10.0.0.3"));
+        assert_eq!(shared.get(b"/web"), Some("10.0.0.4"));
+        assert_eq!(shared.get(b"/static"), None);
+        assert_eq!(old.version(), 0);
+        assert_eq!(old.get(b"/api"), Some(&"10.0.0.1"));
+        assert_eq!(old.get(b"/static"), Some(&"10.0.0.2"));
+        assert_eq!(shared.snapshot().len(), 2);
+
+        let weak = Arc::downgrade(&old);
+        drop(old);
+        assert!(weak.upgrade().is_none());
+    }
+
+    // Dropping a value records whether it was a clone
+    struct Tracked {
+        clone: bool,
+        drops: Arc<Mutex<Vec<bool>>>,
+    }
+
+    impl Clone for Tracked {
+        fn clone(&self) -> Self {
+            Self {
+                clone: true,
+                drops: self.drops.clone(),
+            }
+        }
+    }
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            self.drops.lock().unwrap().push(self.clone);
+        }
+    }
+
+    #[test]
+    fn test_snapshot_stores_values_once() {
+        let drops = Arc::new(Mutex::new(vec![]));
+        let entries: BTreeMap<Box<[u8]>, Tracked> = [&b"/a"[..], b"/b"]
+            .into_iter()
+            .map(|k| {
+                let value = Tracked {
+                    clone: false,
+                    drops: drops.clone(),
+                };
+                (k.into(), value)
+            })
+            .collect();
+        let snapshot: TrieHarderSnapshot<u32, Tracked> = TrieHarderSnapshot::build(entries, 0);
+        assert!(snapshot.get(b"/b").is_some_and(|v| !v.clone));
+        assert!(snapshot.has_prefix(b"/"));
+        assert!(drops.lock().unwrap().is_empty());
+        drop(snapshot);
+        assert_eq!(*drops.lock().unwrap(), [false, false]);
+    }
+
+    #[test]
+    fn test_readers_see_consistent_versions() {
+        const KEYS: [&[u8]; 5] = [b"/a", b"/ab", b"/b", b"/bc/d", b"/c"];
+        const VERSIONS: u64 = 200;
+        let shared: SharedTrieHarderMap<u32, u64> = SharedTrieHarderMap::new(&KEYS, &[0; 5]);
+        let done = AtomicBool::new(false);
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    let mut last = 0;
+                    while !done.load(Ordering::Acquire) {
+                        let snapshot = shared.snapshot();
+                        let version = snapshot.version();
+                        assert!(version >= last);
+                        last = version;
+                        // every key in one snapshot was written by the same batch
+                        for key in KEYS {
+                            assert_eq!(snapshot.get(key), Some(&version));
+                        }
+                        let extra = snapshot.get(b"/extra");
+                        assert_eq!(extra.is_some(), version % 2 == 1);
+                    }
+                });
+            }
+            s.spawn(|| {
+                for version in 1..=VERSIONS {
+                    let mut changes: Vec<_> = KEYS
+                        .iter()
+                        .map(|k| Change::Insert(k.to_vec(), version))
+                        .collect();
+                    changes.push(if version % 2 == 1 {
+                        Change::Insert(b"/extra".to_vec(), version)
+                    } else {
+                        Change::Remove(b"/extra".to_vec())
+                    });
+                    assert_eq!(shared.update(changes), version);
+                }
+                done.store(true, Ordering::Release);
+            });
+        });
+        assert_eq!(shared.version(), VERSIONS);
+    }
+}
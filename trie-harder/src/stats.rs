@@ -0,0 +1,164 @@
+use std::fmt::{Display, Write};
+
+use crate::{TrieHarderMap, TrieNode, UnsignedInt};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrieStats {
+    pub node_count: usize,
+    pub leaf_count: usize,
+    pub max_depth: usize,
+    // children per node that has any
+    pub avg_fan_out: f64,
+    // bytes with an entry in the lookup table
+    pub lookup_entries: usize,
+    // distinct masks handed out, out of `mask_bits`
+    pub mask_bits_used: usize,
+    pub mask_bits: usize,
+    // node vector plus children vectors, values not included
+    pub heap_bytes: usize,
+}
+
+impl Display for TrieStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "nodes:          {}", self.node_count)?;
+        writeln!(f, "leaves:         {}", self.leaf_count)?;
+        writeln!(f, "max depth:      {}", self.max_depth)?;
+        writeln!(f, "avg fan-out:    {:.2}", self.avg_fan_out)?;
+        writeln!(f, "lookup entries: {}/256", self.lookup_entries)?;
+        writeln!(
+            f,
+            "mask bits:      {}/{}",
+            self.mask_bits_used, self.mask_bits
+        )?;
+        write!(f, "heap bytes:     {}", self.heap_bytes)
+    }
+}
+
+fn escape_byte(out: &mut String, c: u8) {
+    match c {
+        b'"' | b'\\' => {
+            out.push('\\');
+            out.push(c as char);
+        }
+        0x20..=0x7e => out.push(c as char),
+        _ => {
+            let _ = write!(out, "\\\\x{c:02x}");
+        }
+    }
+}
+
+impl<T: UnsignedInt, V> TrieHarderMap<'_, T, V> {
+    pub fn stats(&self) -> TrieStats {
+        let mut leaf_count = 0;
+        let mut max_depth = 0;
+        let mut parents = 0;
+        let mut edges = 0;
+        let mut heap_bytes = self.nodes.capacity() * std::mem::size_of::<TrieNode<'_, T, V>>();
+        for node in &self.nodes {
+            if let TrieNode::Leaf(_) = node {
+                leaf_count += 1;
+            }
+            max_depth = max_depth.max(node.string().len());
+            if !node.children().is_empty() {
+                parents += 1;
+                edges += node.children().len();
+            }
+            heap_bytes += match node {
+                TrieNode::Branch(n) => n.children.capacity(),
+                TrieNode::Leaf(n) => n.children.capacity(),
+            } * std::mem::size_of::<usize>();
+        }
+        let mut masks: Vec<_> = self
+            .lookup_table
+            .0
+            .iter()
+            .flatten()
+            .map(|m| m.to_u128())
+            .collect();
+        let lookup_entries = masks.len();
+        masks.sort_unstable();
+        masks.dedup();
+        TrieStats {
+            node_count: self.nodes.len(),
+            leaf_count,
+            max_depth,
+            avg_fan_out: if parents == 0 {
+                0.0
+            } else {
+                edges as f64 / parents as f64
+            },
+            lookup_entries,
+            mask_bits_used: masks.len(),
+            mask_bits: 8 * std::mem::size_of::<T>(),
+            heap_bytes,
+        }
+    }
+
+    // Graphviz DOT with one record per node: its index and mask, leaves drawn
+    // as double circles, edges labelled with the byte they consume.
+    pub fn to_dot(&self) -> String {
+        let width = 8 * std::mem::size_of::<T>();
+        let mut out = String::from("digraph trie {\n    node [shape=circle];\n");
+        for node in &self.nodes {
+            let shape = match node {
+                TrieNode::Branch(_) => "circle",
+                TrieNode::Leaf(_) => "doublecircle",
+            };
+            let _ = writeln!(
+                out,
+                "    n{} [shape={shape}, label=\"{}\\n{:0width$b}\"];",
+                node.index(),
+                node.index(),
+                node.mask().to_u128(),
+            );
+            for &child in node.children() {
+                let string = self.nodes[child].string();
+                let mut label = String::new();
+                escape_byte(&mut label, string[string.len() - 1]);
+                let _ = writeln!(
+                    out,
+                    "    n{} -> n{child} [label=\"{label}\"];",
+                    node.index()
+                );
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{TrieHarderMap, TrieHarderSet};
+
+    #[test]
+    fn test_stats() {
+        let words: [&[u8]; 5] = [b"and", b"ant", b"dad", b"do", b"dot"];
+        let th: TrieHarderSet<'_, u8> = TrieHarderSet::from_strs(&words);
+        let stats = th.stats();
+        assert_eq!(stats.node_count, 10);
+        assert_eq!(stats.leaf_count, 5);
+        assert_eq!(stats.max_depth, 3);
+        // root:2, a:1, an:2, d:2, da:1, do:1
+        assert_eq!(stats.avg_fan_out, 9.0 / 6.0);
+        assert_eq!(stats.lookup_entries, 5);
+        assert_eq!(stats.mask_bits_used, 5);
+        assert_eq!(stats.mask_bits, 8);
+        assert!(stats.heap_bytes >= 10 * std::mem::size_of::<crate::TrieNode<'_, u8, ()>>());
+        assert!(stats.to_string().contains("mask bits:      5/8"));
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let words: [&[u8]; 3] = [b"a\"", b"\x00", b"ab"];
+        let th: TrieHarderMap<'_, u128, u8> =
+            TrieHarderMap::from_strs_and_values(&words, &[0, 1, 2]);
+        let dot = th.to_dot();
+        assert!(dot.starts_with("digraph trie {"));
+        assert!(dot.contains("n0 -> n1 [label=\"a\"];"));
+        assert!(dot.contains("[label=\"\\\"\"]"));
+        assert!(dot.contains("[label=\"\\\\x00\"]"));
+        assert_eq!(dot.matches("doublecircle").count(), 3);
+        assert!(dot.contains(&format!("n0 [shape=circle, label=\"0\\n{:0128b}\"];", 0b11)));
+    }
+}
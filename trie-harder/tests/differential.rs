@@ -0,0 +1,126 @@
+use std::collections::BTreeMap;
+
+use proptest::{collection::vec, prelude::*};
+use trie_harder::{TrieHarderMap, TrieHarderView};
+
+// A random key set over an alphabet no wider than the mask type, plus
+// prefixes and duplicates of some keys, and the queries to run against it.
+#[derive(Debug, Clone)]
+struct Case {
+    keys: Vec<Vec<u8>>,
+    queries: Vec<Vec<u8>>,
+}
+
+fn case(max_alphabet: usize) -> impl Strategy<Value = Case> {
+    (
+        prop::collection::btree_set(any::<u8>(), 1..=max_alphabet),
+        vec(vec(any::<prop::sample::Index>(), 0..8), 0..24),
+        vec(
+            (any::<prop::sample::Index>(), any::<prop::sample::Index>()),
+            0..8,
+        ),
+        vec(vec(any::<u8>(), 0..6), 0..16),
+    )
+        .prop_map(|(alphabet, raw_keys, extras, random_queries)| {
+            let alphabet: Vec<u8> = alphabet.into_iter().collect();
+            let mut keys: Vec<Vec<u8>> = raw_keys
+                .iter()
+                .map(|key| key.iter().map(|i| *i.get(&alphabet)).collect())
+                .collect();
+            if !keys.is_empty() {
+                // prefixes (possibly empty or the whole key, i.e. duplicates)
+                for (key, cut) in extras {
+                    let key = key.get(&keys).clone();
+                    let cut = cut.index(key.len() + 1);
+                    keys.push(key[..cut].to_vec());
+                }
+            }
+            let mut queries = random_queries;
+            for key in &keys {
+                for end in 0..=key.len() {
+                    queries.push(key[..end].to_vec());
+                }
+                for &c in &alphabet {
+                    let mut longer = key.clone();
+                    longer.push(c);
+                    queries.push(longer);
+                }
+            }
+            Case { keys, queries }
+        })
+}
+
+fn oracle(keys: &[Vec<u8>]) -> BTreeMap<&[u8], u64> {
+    let mut oracle = BTreeMap::new();
+    for (i, key) in keys.iter().enumerate() {
+        oracle.insert(key.as_slice(), i as u64);
+    }
+    oracle
+}
+
+fn oracle_has_prefix(oracle: &BTreeMap<&[u8], u64>, query: &[u8]) -> bool {
+    !oracle.contains_key(query)
+        && oracle
+            .range(query..)
+            .next()
+            .is_some_and(|(k, _)| k.starts_with(query))
+}
+
+macro_rules! differential {
+    ($($name: ident: $t: ty),*) => {
+        $(
+            proptest! {
+                #[test]
+                fn $name(case in case(8 * std::mem::size_of::<$t>())) {
+                    let keys: Vec<&[u8]> = case.keys.iter().map(|k| k.as_slice()).collect();
+                    let values: Vec<u64> = (0..keys.len() as u64).collect();
+                    let th: TrieHarderMap<'_, $t, u64> =
+                        TrieHarderMap::from_strs_and_values(&keys, &values);
+                    let oracle = oracle(&case.keys);
+                    let bytes = th.to_bytes();
+                    let view = TrieHarderView::from_bytes(&bytes).unwrap();
+                    for query in &case.queries {
+                        prop_assert_eq!(th.get(query), oracle.get(query.as_slice()));
+                        prop_assert_eq!(th.has_prefix(query), oracle_has_prefix(&oracle, query));
+                        prop_assert_eq!(view.get_as::<u64>(query), oracle.get(query.as_slice()).copied());
+                        prop_assert_eq!(view.has_prefix(query), oracle_has_prefix(&oracle, query));
+                    }
+                    let entries: Vec<_> = th.iter().map(|(k, &v)| (k, v)).collect();
+                    let expected: Vec<_> = oracle.into_iter().collect();
+                    prop_assert_eq!(entries, expected);
+                }
+            }
+        )*
+    };
+}
+
+differential!(
+    differential_u8: u8,
+    differential_u16: u16,
+    differential_u32: u32,
+    differential_u64: u64,
+    differential_u128: u128
+);
+
+#[test]
+fn all_byte_values() {
+    // 256 distinct bytes never fit one mask, so cover them in two halves
+    for half in [0..=127_u8, 128..=255] {
+        let keys: Vec<Vec<u8>> = half.map(|c| vec![c, c]).collect();
+        let keys: Vec<&[u8]> = keys.iter().map(|k| k.as_slice()).collect();
+        let values: Vec<usize> = (0..keys.len()).collect();
+        let th: TrieHarderMap<'_, u128, usize> =
+            TrieHarderMap::from_strs_and_values(&keys, &values);
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(th.get(key), Some(&i));
+            assert!(th.has_prefix(&key[..1]));
+        }
+    }
+}
+
+#[test]
+#[should_panic(expected = "more distinct bytes than bits in the mask type")]
+fn too_many_distinct_bytes() {
+    let keys: [&[u8]; 1] = [b"abcdefghi"];
+    let _: TrieHarderMap<'_, u8, ()> = TrieHarderMap::from_strs_and_values(&keys, &[()]);
+}